@@ -0,0 +1,202 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{from_str, AppTokenInfo, Client, LoginTvQrInfo, Result, WebToken};
+
+/// TV扫码登录得到的令牌
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TvToken {
+    pub mid: i64,
+    pub access_token: String,
+    pub refresh_token: String,
+    /// 过期时间 (秒级时间戳), 由登录时返回的expires_in换算
+    pub expires_at: i64,
+}
+
+impl TvToken {
+    /// 令牌是否已经过期
+    pub fn expired(&self) -> bool {
+        self.expires_at <= chrono::Local::now().timestamp()
+    }
+}
+
+impl From<&LoginTvQrInfo> for TvToken {
+    fn from(info: &LoginTvQrInfo) -> Self {
+        Self {
+            mid: info.mid,
+            access_token: info.access_token.clone(),
+            refresh_token: info.refresh_token.clone(),
+            expires_at: chrono::Local::now().timestamp() + info.expires_in,
+        }
+    }
+}
+
+impl From<&AppTokenInfo> for TvToken {
+    fn from(info: &AppTokenInfo) -> Self {
+        Self {
+            mid: info.mid,
+            access_token: info.access_token.clone(),
+            refresh_token: info.refresh_token.clone(),
+            expires_at: chrono::Local::now().timestamp() + info.expires_in,
+        }
+    }
+}
+
+/// 登录凭据, 用于保存登录状态, 避免每次启动都重新扫码
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Credential {
+    #[serde(default)]
+    pub web_token: Option<WebToken>,
+    /// WEB登录时返回的refresh_token, 用于刷新Cookie
+    #[serde(default)]
+    pub refresh_token: Option<String>,
+    #[serde(default)]
+    pub buvid3: Option<String>,
+    #[serde(default)]
+    pub buvid4: Option<String>,
+    #[serde(default)]
+    pub tv_token: Option<TvToken>,
+}
+
+impl Credential {
+    /// WEB登录是否已经过期 (未登录也视为过期), 根据WebToken.expires判断
+    pub fn is_expired(&self) -> bool {
+        match &self.web_token {
+            Some(web_token) => web_token.expires <= chrono::Local::now().timestamp(),
+            None => true,
+        }
+    }
+
+    /// TV登录是否已经过期 (未登录也视为过期)
+    pub fn is_tv_expired(&self) -> bool {
+        match &self.tv_token {
+            Some(tv_token) => tv_token.expired(),
+            None => true,
+        }
+    }
+}
+
+/// 登录凭据的存储
+pub trait CredentialStore {
+    /// 读取凭据, 不存在时返回None
+    fn load(&self) -> Result<Option<Credential>>;
+
+    /// 保存凭据
+    fn save(&self, credential: &Credential) -> Result<()>;
+
+    /// 删除凭据
+    fn clear(&self) -> Result<()>;
+}
+
+/// 使用JSON文件存储登录凭据
+#[derive(Debug, Clone)]
+pub struct FileCredentialStore {
+    path: PathBuf,
+}
+
+impl FileCredentialStore {
+    /// 构造方法
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// 文件路径
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl CredentialStore for FileCredentialStore {
+    fn load(&self) -> Result<Option<Credential>> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(json) => Ok(Some(from_str(&json)?)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn save(&self, credential: &Credential) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            if !parent.as_os_str().is_empty() {
+                std::fs::create_dir_all(parent)?;
+            }
+        }
+        // 先写入临时文件再重命名, 避免写入过程中中断导致凭据损坏
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(&tmp)?;
+        std::io::Write::write_all(
+            &mut file,
+            serde_json::to_string_pretty(credential)?.as_bytes(),
+        )?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<()> {
+        match std::fs::remove_file(&self.path) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// 登录凭据
+impl Client {
+    /// 当前的登录凭据
+    pub fn credential(&self) -> Credential {
+        let session = self.session();
+        Credential {
+            web_token: session.web_token(),
+            refresh_token: session.refresh_token().map(str::to_string),
+            buvid3: session.buvid3().map(str::to_string),
+            buvid4: session.buvid4().map(str::to_string),
+            tv_token: session.tv_token().cloned(),
+        }
+    }
+
+    /// 注入登录凭据
+    pub fn set_credential(&mut self, credential: &Credential) {
+        let mut session = self.session();
+        if let Some(web_token) = &credential.web_token {
+            session.set_web_token(web_token);
+        }
+        if let Some(refresh_token) = &credential.refresh_token {
+            session.set_refresh_token(refresh_token.as_str());
+        }
+        if let Some(buvid3) = &credential.buvid3 {
+            session.set(crate::COOKIE_BUVID3, buvid3.as_str());
+        }
+        if let Some(buvid4) = &credential.buvid4 {
+            session.set(crate::COOKIE_BUVID4, buvid4.as_str());
+        }
+        if let Some(tv_token) = &credential.tv_token {
+            session.set_tv_token(tv_token.clone());
+        }
+        self.set_session(session);
+    }
+
+    /// 保存当前的登录凭据
+    pub fn save_credential(&self, store: &impl CredentialStore) -> Result<()> {
+        store.save(&self.credential())
+    }
+
+    /// 读取登录凭据并注入, 返回读取到的凭据, 调用者可以通过is_expired判断是否需要重新登录
+    pub fn load_credential(&mut self, store: &impl CredentialStore) -> Result<Option<Credential>> {
+        let credential = store.load()?;
+        if let Some(credential) = &credential {
+            self.set_credential(credential);
+        }
+        Ok(credential)
+    }
+}
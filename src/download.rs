@@ -0,0 +1,427 @@
+//! 下载DASH/FLV/MP4流, 支持分块并发, 断点续传与备用地址重试
+
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+use tracing::debug;
+
+use crate::{ordered_urls, Audio, Client, Durl, Error, Result, Video};
+
+const REFERER: &str = "https://www.bilibili.com";
+
+/// 可以下载的流
+pub trait DownloadSource {
+    /// 下载地址, 主地址在前, 失败时依次使用后面的地址
+    fn download_urls(&self) -> Vec<String>;
+}
+
+impl DownloadSource for Video {
+    fn download_urls(&self) -> Vec<String> {
+        self.urls()
+    }
+}
+
+impl DownloadSource for Audio {
+    fn download_urls(&self) -> Vec<String> {
+        self.urls()
+    }
+}
+
+impl DownloadSource for Durl {
+    fn download_urls(&self) -> Vec<String> {
+        ordered_urls(&self.url, &self.backup_url, &[])
+    }
+}
+
+impl DownloadSource for Vec<String> {
+    fn download_urls(&self) -> Vec<String> {
+        self.clone()
+    }
+}
+
+/// 下载进度
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub struct DownloadProgress {
+    /// 已下载的字节数 (包含续传前已经下载的部分)
+    pub downloaded: u64,
+    /// 总字节数
+    pub total: u64,
+    /// 本次下载的平均速度 (字节/秒)
+    pub speed: f64,
+}
+
+type ProgressCallback = Arc<dyn Fn(DownloadProgress) + Send + Sync>;
+
+/// 续传状态, 保存在下载文件旁的 .part.json 中
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct DownloadState {
+    total: u64,
+    chunk_size: u64,
+    done: Vec<bool>,
+}
+
+/// 下载器
+/// 会携带客户端的UA与B站的Referer, 否则CDN会返回403
+#[derive(Clone)]
+pub struct Downloader {
+    agent: reqwest::Client,
+    referer: String,
+    chunk_size: u64,
+    concurrency: usize,
+    retries: usize,
+    progress: Option<ProgressCallback>,
+}
+
+impl Downloader {
+    /// 构造方法, 默认每块4MiB, 4个并发, 每块重试3次
+    pub fn new(client: &Client) -> Self {
+        Self {
+            agent: client.agent.clone(),
+            referer: REFERER.to_string(),
+            chunk_size: 4 * 1024 * 1024,
+            concurrency: 4,
+            retries: 3,
+            progress: None,
+        }
+    }
+
+    /// Referer
+    pub fn referer(mut self, referer: impl Into<String>) -> Self {
+        self.referer = referer.into();
+        self
+    }
+
+    /// 每块的字节数
+    pub fn chunk_size(mut self, chunk_size: u64) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// 同时下载的块数
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// 每块的重试次数, 每次重试会换用下一个地址
+    pub fn retries(mut self, retries: usize) -> Self {
+        self.retries = retries.max(1);
+        self
+    }
+
+    /// 进度回调, 每收到一段数据调用一次
+    pub fn progress(mut self, progress: impl Fn(DownloadProgress) + Send + Sync + 'static) -> Self {
+        self.progress = Some(Arc::new(progress));
+        self
+    }
+
+    /// 下载到文件, 返回文件大小
+    /// 下载过程中写入 {path}.part, 中断后再次调用会从已完成的块继续
+    pub async fn download(
+        &self,
+        source: &impl DownloadSource,
+        path: impl AsRef<Path>,
+    ) -> Result<u64> {
+        let urls = source.download_urls();
+        if urls.is_empty() {
+            return Err(Error::InvalidArgument("没有可用的下载地址".to_string()));
+        }
+        let path = path.as_ref();
+        let part_path = with_suffix(path, ".part");
+        let state_path = with_suffix(path, ".part.json");
+        let total = match self.probe(&urls).await? {
+            Some(total) => total,
+            None => {
+                // 服务器不支持Range, 只能整体下载
+                let total = self.download_whole(&urls, &part_path).await?;
+                tokio::fs::rename(&part_path, path).await?;
+                return Ok(total);
+            }
+        };
+
+        let chunks = total.div_ceil(self.chunk_size) as usize;
+        let mut state = load_state(&state_path, &part_path, total, self.chunk_size)
+            .await
+            .unwrap_or_else(|| DownloadState {
+                total,
+                chunk_size: self.chunk_size,
+                done: vec![false; chunks],
+            });
+        let file = tokio::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&part_path)
+            .await?;
+        file.set_len(total).await?;
+
+        let resumed: u64 = (0..chunks)
+            .filter(|index| state.done[*index])
+            .map(|index| {
+                let (start, end) = self.chunk_range(index, total);
+                end - start + 1
+            })
+            .sum();
+        let downloaded = AtomicU64::new(resumed);
+        let started = Instant::now();
+        let report = |delta: i64| {
+            let now = if delta >= 0 {
+                downloaded.fetch_add(delta as u64, Ordering::SeqCst) + delta as u64
+            } else {
+                downloaded.fetch_sub((-delta) as u64, Ordering::SeqCst) - (-delta) as u64
+            };
+            if let Some(progress) = &self.progress {
+                let elapsed = started.elapsed().as_secs_f64();
+                progress(DownloadProgress {
+                    downloaded: now,
+                    total,
+                    speed: if elapsed > 0.0 {
+                        now.saturating_sub(resumed) as f64 / elapsed
+                    } else {
+                        0.0
+                    },
+                });
+            }
+        };
+
+        let pending: Vec<usize> = (0..chunks).filter(|index| !state.done[*index]).collect();
+        let mut results = futures::stream::iter(pending)
+            .map(|index| {
+                let (start, end) = self.chunk_range(index, total);
+                let (urls, part_path, report) = (&urls, &part_path, &report);
+                async move {
+                    self.download_chunk(urls, index, start, end, part_path, report)
+                        .await
+                        .map(|_| index)
+                }
+            })
+            .buffer_unordered(self.concurrency);
+        while let Some(result) = results.next().await {
+            let index = result?;
+            state.done[index] = true;
+            save_state(&state_path, &state).await?;
+        }
+        drop(results);
+
+        file.sync_all().await?;
+        drop(file);
+        tokio::fs::rename(&part_path, path).await?;
+        let _ = tokio::fs::remove_file(&state_path).await;
+        Ok(total)
+    }
+
+    fn chunk_range(&self, index: usize, total: u64) -> (u64, u64) {
+        let start = index as u64 * self.chunk_size;
+        (start, (start + self.chunk_size).min(total) - 1)
+    }
+
+    fn request(&self, url: &str) -> reqwest::RequestBuilder {
+        self.agent
+            .get(url)
+            .header(reqwest::header::REFERER, self.referer.as_str())
+    }
+
+    /// 获取文件大小, 服务器不支持Range或没有返回总大小时返回None
+    async fn probe(&self, urls: &[String]) -> Result<Option<u64>> {
+        let mut last_error = None;
+        for url in urls {
+            debug!(target = "network", "Probe : {}", url);
+            let resp = match self
+                .request(url)
+                .header(reqwest::header::RANGE, "bytes=0-0")
+                .send()
+                .await
+                .and_then(|resp| resp.error_for_status())
+            {
+                Ok(resp) => resp,
+                Err(err) => {
+                    last_error = Some(err.into());
+                    continue;
+                }
+            };
+            if resp.status() != reqwest::StatusCode::PARTIAL_CONTENT {
+                return Ok(None);
+            }
+            // Content-Range: bytes 0-0/12345, 总大小未知时为 bytes 0-0/*
+            return Ok(resp
+                .headers()
+                .get(reqwest::header::CONTENT_RANGE)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.rsplit('/').next())
+                .and_then(|total| total.trim().parse().ok()));
+        }
+        Err(last_error.unwrap_or_else(no_url))
+    }
+
+    async fn download_chunk(
+        &self,
+        urls: &[String],
+        index: usize,
+        start: u64,
+        end: u64,
+        part_path: &Path,
+        report: &(impl Fn(i64) + Sync),
+    ) -> Result<()> {
+        let mut last_error = None;
+        for attempt in 0..self.retries {
+            let url = url_at(urls, attempt)?;
+            let mut received = 0;
+            match self
+                .fetch_range(url, start, end, part_path, &mut received, report)
+                .await
+            {
+                Ok(()) => return Ok(()),
+                Err(err) => {
+                    debug!(
+                        target = "network",
+                        "Chunk {} failed : {} ({})", index, url, err
+                    );
+                    report(-(received as i64));
+                    last_error = Some(err);
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(no_url))
+    }
+
+    /// 下载一块, 收到的数据直接写入文件中该块的位置
+    async fn fetch_range(
+        &self,
+        url: &str,
+        start: u64,
+        end: u64,
+        part_path: &Path,
+        received: &mut u64,
+        report: &impl Fn(i64),
+    ) -> Result<()> {
+        let mut resp = self
+            .request(url)
+            .header(reqwest::header::RANGE, format!("bytes={}-{}", start, end))
+            .send()
+            .await?
+            .error_for_status()?;
+        if resp.status() != reqwest::StatusCode::PARTIAL_CONTENT {
+            return Err(Error::Format("服务器不支持Range请求".to_string()));
+        }
+        let expected = end - start + 1;
+        let mut file = tokio::fs::OpenOptions::new()
+            .write(true)
+            .open(part_path)
+            .await?;
+        file.seek(SeekFrom::Start(start)).await?;
+        while let Some(bytes) = resp.chunk().await? {
+            if *received + bytes.len() as u64 > expected {
+                return Err(Error::Format(format!(
+                    "分块大小不一致: {}-{} 收到超过{}字节",
+                    start, end, expected
+                )));
+            }
+            file.write_all(&bytes).await?;
+            *received += bytes.len() as u64;
+            report(bytes.len() as i64);
+        }
+        file.flush().await?;
+        if *received != expected {
+            return Err(Error::Format(format!(
+                "分块大小不一致: {}-{} 收到{}字节",
+                start, end, received
+            )));
+        }
+        Ok(())
+    }
+
+    async fn download_whole(&self, urls: &[String], part_path: &Path) -> Result<u64> {
+        let mut last_error = None;
+        for attempt in 0..self.retries {
+            let url = url_at(urls, attempt)?;
+            match self.fetch_whole(url, part_path).await {
+                Ok(total) => return Ok(total),
+                Err(err) => last_error = Some(err),
+            }
+        }
+        Err(last_error.unwrap_or_else(no_url))
+    }
+
+    async fn fetch_whole(&self, url: &str, part_path: &Path) -> Result<u64> {
+        let mut resp = self.request(url).send().await?.error_for_status()?;
+        let total = resp.content_length().unwrap_or_default();
+        let mut file = tokio::fs::File::create(part_path).await?;
+        let started = Instant::now();
+        let mut downloaded = 0;
+        while let Some(bytes) = resp.chunk().await? {
+            file.write_all(&bytes).await?;
+            downloaded += bytes.len() as u64;
+            if let Some(progress) = &self.progress {
+                let elapsed = started.elapsed().as_secs_f64();
+                progress(DownloadProgress {
+                    downloaded,
+                    total: total.max(downloaded),
+                    speed: if elapsed > 0.0 {
+                        downloaded as f64 / elapsed
+                    } else {
+                        0.0
+                    },
+                });
+            }
+        }
+        file.sync_all().await?;
+        Ok(downloaded)
+    }
+}
+
+fn no_url() -> Error {
+    Error::InvalidArgument("没有可用的下载地址".to_string())
+}
+
+/// 第attempt次尝试使用的地址, 依次轮换
+fn url_at(urls: &[String], attempt: usize) -> Result<&String> {
+    if urls.is_empty() {
+        return Err(no_url());
+    }
+    Ok(&urls[attempt % urls.len()])
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+/// 读取续传状态, 文件大小或分块大小变化时不能续传
+async fn load_state(
+    state_path: &Path,
+    part_path: &Path,
+    total: u64,
+    chunk_size: u64,
+) -> Option<DownloadState> {
+    let state: DownloadState =
+        serde_json::from_str(&tokio::fs::read_to_string(state_path).await.ok()?).ok()?;
+    let part_len = tokio::fs::metadata(part_path).await.ok()?.len();
+    if state.total != total
+        || state.chunk_size != chunk_size
+        || part_len != total
+        || state.done.len() as u64 != total.div_ceil(chunk_size)
+    {
+        return None;
+    }
+    Some(state)
+}
+
+async fn save_state(state_path: &Path, state: &DownloadState) -> Result<()> {
+    tokio::fs::write(state_path, serde_json::to_string(state)?).await?;
+    Ok(())
+}
+
+/// 下载
+impl Client {
+    /// 使用当前客户端的设置创建下载器
+    pub fn downloader(&self) -> Downloader {
+        Downloader::new(self)
+    }
+}
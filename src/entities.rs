@@ -68,6 +68,70 @@ macro_rules! enum_str {
     }
 }
 
+/// 使用数字编码的枚举, 按声明的顺序排序
+macro_rules! enum_code {
+    ($name:ident { $($variant:ident($code:expr, $desc:expr), )* }) => {
+        #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, PartialOrd, Ord)]
+        pub enum $name {
+            $($variant,)*
+        }
+
+        impl $name {
+            /// 接口中使用的编码
+            pub fn code(&self) -> i64 {
+                match self {
+                    $( $name::$variant => $code, )*
+                }
+            }
+
+            /// 说明
+            pub fn description(&self) -> &'static str {
+                match self {
+                    $( $name::$variant => $desc, )*
+                }
+            }
+        }
+
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "{}", self.description())
+            }
+        }
+
+        impl TryFrom<i64> for $name {
+            type Error = crate::Error;
+
+            fn try_from(code: i64) -> crate::Result<Self> {
+                match code {
+                    $( $code => Ok($name::$variant), )*
+                    _ => Err(crate::Error::InvalidArgument(format!(
+                        "unknown {} code: {}",
+                        stringify!($name),
+                        code
+                    ))),
+                }
+            }
+        }
+
+        impl ::serde::Serialize for $name {
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+                where S: ::serde::Serializer,
+            {
+                serializer.serialize_i64(self.code())
+            }
+        }
+
+        impl<'de> ::serde::Deserialize<'de> for $name {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+                where D: ::serde::Deserializer<'de>,
+            {
+                let code = i64::deserialize(deserializer)?;
+                $name::try_from(code).map_err(::serde::de::Error::custom)
+            }
+        }
+    }
+}
+
 /////////////////////
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -84,6 +148,15 @@ pub struct WebToken {
     pub expires: i64,
 }
 
+/// 设备标识
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FingerSpi {
+    #[serde(default = "default_string")]
+    pub b_3: String,
+    #[serde(default = "default_string")]
+    pub b_4: String,
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct LoginQrData {
     #[serde(default = "default_string")]
@@ -92,6 +165,76 @@ pub struct LoginQrData {
     pub oauth_key: String,
 }
 
+/// WEB二维码登录 (passport-login)
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WebQrData {
+    #[serde(default = "default_string")]
+    pub url: String,
+    #[serde(default = "default_string")]
+    pub qrcode_key: String,
+}
+
+/// WEB二维码登录的扫码状态
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WebQrStatus {
+    /// 0: 登录成功
+    Confirmed,
+    /// 86038: 二维码已失效
+    Expired,
+    /// 86090: 已扫码未确认
+    Scanned,
+    /// 86101: 未扫码
+    NotScanned,
+    /// 其他状态
+    Unknown(i64),
+}
+
+impl WebQrStatus {
+    pub fn from_code(code: i64) -> Self {
+        match code {
+            0 => WebQrStatus::Confirmed,
+            86038 => WebQrStatus::Expired,
+            86090 => WebQrStatus::Scanned,
+            86101 => WebQrStatus::NotScanned,
+            code => WebQrStatus::Unknown(code),
+        }
+    }
+
+    pub fn code(&self) -> i64 {
+        match self {
+            WebQrStatus::Confirmed => 0,
+            WebQrStatus::Expired => 86038,
+            WebQrStatus::Scanned => 86090,
+            WebQrStatus::NotScanned => 86101,
+            WebQrStatus::Unknown(code) => *code,
+        }
+    }
+}
+
+/// WEB二维码登录的扫码结果
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WebQrPoll {
+    #[serde(default = "default_string")]
+    pub url: String,
+    #[serde(default = "default_string")]
+    pub refresh_token: String,
+    #[serde(default = "default_i64")]
+    pub timestamp: i64,
+    #[serde(default = "default_i64")]
+    pub code: i64,
+    #[serde(default = "default_string")]
+    pub message: String,
+    /// 登录成功时从响应的Cookie中得到的token
+    #[serde(skip)]
+    pub token: Option<WebToken>,
+}
+
+impl WebQrPoll {
+    pub fn status(&self) -> WebQrStatus {
+        WebQrStatus::from_code(self.code)
+    }
+}
+
 /// 因为API并不规范, 未登录成功返回数字, 登录成功返回字典, 所以进行了二次封装
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct LoginQrInfo {
@@ -105,6 +248,152 @@ pub struct LoginQrInfo {
     pub url: String,
 }
 
+/// 登录时申请的人机验证
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Captcha {
+    #[serde(default = "default_string", rename = "type")]
+    pub captcha_type: String,
+    #[serde(default = "default_string")]
+    pub token: String,
+    #[serde(default)]
+    pub geetest: Geetest,
+}
+
+/// 极验验证码的参数, 交给GeetestSolver完成验证
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Geetest {
+    #[serde(default = "default_string")]
+    pub gt: String,
+    #[serde(default = "default_string")]
+    pub challenge: String,
+}
+
+/// 极验验证码的验证结果
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GeetestResult {
+    pub challenge: String,
+    pub validate: String,
+    pub seccode: String,
+}
+
+/// 发送短信验证码的结果
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SmsSend {
+    #[serde(default = "default_string")]
+    pub captcha_key: String,
+}
+
+/// 密码登录时加密密码使用的公钥与盐
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LoginKey {
+    #[serde(default = "default_string")]
+    pub hash: String,
+    #[serde(default = "default_string")]
+    pub key: String,
+}
+
+/// 短信/密码登录的结果
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WebLoginResult {
+    // 0: 成功
+    // 其他: 需要进一步验证 (如绑定手机), url为验证页面
+    #[serde(default = "default_i64")]
+    pub status: i64,
+    #[serde(default = "default_string")]
+    pub message: String,
+    #[serde(default = "default_string")]
+    pub url: String,
+    #[serde(default = "default_string")]
+    pub refresh_token: String,
+    #[serde(default = "default_i64")]
+    pub timestamp: i64,
+    /// 登录成功时从响应的Cookie中得到的token
+    #[serde(skip)]
+    pub token: Option<WebToken>,
+}
+
+/// WBI签名使用的图片地址, 文件名即为密钥
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WbiImg {
+    #[serde(default = "default_string")]
+    pub img_url: String,
+    #[serde(default = "default_string")]
+    pub sub_url: String,
+}
+
+/// 导航栏用户信息 (/x/web-interface/nav), 未登录时仅返回is_login与wbi_img
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Nav {
+    #[serde(default = "default_bool", rename = "isLogin")]
+    pub is_login: bool,
+    #[serde(default = "default_i64")]
+    pub mid: i64,
+    #[serde(default = "default_string")]
+    pub uname: String,
+    #[serde(default = "default_string")]
+    pub face: String,
+    /// 硬币数
+    #[serde(default)]
+    pub money: f64,
+    // 0: 无
+    // 1: 月度大会员
+    // 2: 年度及以上大会员
+    #[serde(default = "default_i64", rename = "vipType")]
+    pub vip_type: i64,
+    #[serde(default = "default_i64", rename = "vipStatus")]
+    pub vip_status: i64,
+    /// 大会员过期时间 (毫秒级时间戳)
+    #[serde(default = "default_i64", rename = "vipDueDate")]
+    pub vip_due_date: i64,
+    #[serde(default = "Default::default")]
+    pub wallet: NavWallet,
+    #[serde(default = "Default::default")]
+    pub wbi_img: WbiImg,
+}
+
+impl Nav {
+    /// 是否为有效的大会员
+    pub fn is_vip(&self) -> bool {
+        self.vip_status == 1
+    }
+}
+
+/// 导航栏用户信息中的钱包
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NavWallet {
+    #[serde(default = "default_i64")]
+    pub mid: i64,
+    /// B币余额
+    #[serde(default)]
+    pub bcoin_balance: f64,
+    /// B币券余额
+    #[serde(default)]
+    pub coupon_balance: f64,
+    #[serde(default = "default_i64")]
+    pub coupon_due_time: i64,
+}
+
+/// 检查Cookie是否需要刷新
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CookieInfo {
+    #[serde(default = "default_bool")]
+    pub refresh: bool,
+    /// 毫秒级时间戳
+    #[serde(default = "default_i64")]
+    pub timestamp: i64,
+}
+
+/// 刷新Cookie的结果
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CookieRefresh {
+    #[serde(default = "default_i64")]
+    pub status: i64,
+    #[serde(default = "default_string")]
+    pub message: String,
+    #[serde(default = "default_string")]
+    pub refresh_token: String,
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Response<T> {
     #[serde(default = "default_i64")]
@@ -113,7 +402,8 @@ pub struct Response<T> {
     pub message: String,
     #[serde(default = "default_i64")]
     pub ttl: i64,
-    #[serde(default = "default_option")]
+    /// PGC接口返回的是result
+    #[serde(default = "default_option", alias = "result")]
     pub data: Option<T>,
 }
 
@@ -476,6 +766,56 @@ pub struct VideoUrl {
     pub support_formats: Vec<SupportFormat>,
     #[serde(default = "default_dash")]
     pub dash: Dash,
+    /// PGC: 是否为试看 (1: 只能获取到试看片段)
+    #[serde(default = "default_i64")]
+    pub is_preview: i64,
+    /// PGC: 片头片尾等片段的时间
+    #[serde(default = "default_vec", deserialize_with = "null_vec")]
+    pub clip_info_list: Vec<ClipInfo>,
+    /// PGC: 备案号等信息
+    #[serde(default = "default_string")]
+    pub record: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClipInfo {
+    #[serde(rename = "materialNo", default = "default_i64")]
+    pub material_no: i64,
+    /// 开始时间 (秒)
+    #[serde(default = "default_i64")]
+    pub start: i64,
+    /// 结束时间 (秒)
+    #[serde(default = "default_i64")]
+    pub end: i64,
+    /// CLIP_TYPE_OP: 片头, CLIP_TYPE_ED: 片尾
+    #[serde(rename = "clipType", default = "default_string")]
+    pub clip_type: String,
+    #[serde(rename = "toastText", default = "default_string")]
+    pub toast_text: String,
+}
+
+/// PGC v2取流接口的结果
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PgcPlayUrlV2 {
+    #[serde(default = "Default::default")]
+    pub video_info: VideoUrl,
+}
+
+impl VideoUrl {
+    /// 是否为试看
+    pub fn is_preview(&self) -> bool {
+        self.is_preview == 1
+    }
+
+    /// 当前画质
+    pub fn video_quality(&self) -> VideoQuality {
+        VideoQuality::from_code(self.quality)
+    }
+
+    /// 当前编码, 未知的编码为None
+    pub fn video_codec(&self) -> Option<VideoCodec> {
+        VideoCodec::try_from(self.video_codecid).ok()
+    }
 }
 
 fn default_dash() -> Dash {
@@ -530,7 +870,44 @@ pub struct Dash {
     pub min_buffer_time2: f64,
     pub video: Vec<Video>,
     pub audio: Vec<Audio>,
-    // pub dolby: Value,
+    /// 杜比音频, fnval需要包含FNVAL_DASH_DB
+    #[serde(default = "default_option")]
+    pub dolby: Option<DashDolby>,
+    /// Hi-Res无损音频
+    #[serde(default = "default_option")]
+    pub flac: Option<DashFlac>,
+}
+
+impl Dash {
+    /// 所有音频流: 普通音频, 杜比音频, Hi-Res无损音频
+    pub fn all_audio(&self) -> Vec<Audio> {
+        let mut audios = self.audio.clone();
+        if let Some(dolby) = &self.dolby {
+            audios.extend(dolby.audio.iter().cloned());
+        }
+        if let Some(Some(audio)) = self.flac.as_ref().map(|flac| &flac.audio) {
+            audios.push(audio.clone());
+        }
+        audios
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DashDolby {
+    /// 1: 普通杜比音效, 2: 杜比全景声
+    #[serde(rename = "type", default = "default_i64")]
+    pub dolby_type: i64,
+    #[serde(default = "default_vec", deserialize_with = "null_vec")]
+    pub audio: Vec<Audio>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DashFlac {
+    /// 是否在播放器中显示Hi-Res无损选项
+    #[serde(default = "default_bool")]
+    pub display: bool,
+    #[serde(default = "default_option")]
+    pub audio: Option<Audio>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -570,7 +947,9 @@ pub struct Video {
     pub codecid: i64,
 }
 
+/// 杜比与Hi-Res音频只有部分字段, 缺少的字段使用默认值
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Audio {
     pub id: i64,
     pub base_url: String,
@@ -607,12 +986,436 @@ pub struct Audio {
     pub codecid: i64,
 }
 
+impl Video {
+    /// 画质
+    pub fn quality(&self) -> VideoQuality {
+        VideoQuality::from_code(self.id)
+    }
+
+    /// 编码, 未知的编码为None
+    pub fn codec(&self) -> Option<VideoCodec> {
+        VideoCodec::try_from(self.codecid).ok()
+    }
+}
+
+impl Audio {
+    /// 音质, 未知的音质为None
+    pub fn quality(&self) -> Option<AudioQuality> {
+        AudioQuality::try_from(self.id).ok()
+    }
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SegmentBase {
     pub initialization: String,
     pub index_range: String,
 }
 
+/// 番剧/电影 (PGC) 的剧集信息, /pgc/view/web/season
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PgcSeason {
+    pub season_id: i64,
+    pub media_id: i64,
+    /// 1: 番剧, 2: 电影, 3: 纪录片, 4: 国创, 5: 电视剧, 7: 综艺
+    #[serde(rename = "type")]
+    pub season_type: i64,
+    pub title: String,
+    pub season_title: String,
+    pub cover: String,
+    pub square_cover: String,
+    pub evaluate: String,
+    pub jp_title: String,
+    pub alias: String,
+    pub record: String,
+    pub actors: String,
+    pub staff: String,
+    pub link: String,
+    pub share_url: String,
+    pub status: i64,
+    pub total: i64,
+    pub styles: Vec<String>,
+    pub areas: Vec<web::Area>,
+    /// 正片
+    pub episodes: Vec<PgcEpisode>,
+    /// PV/花絮等
+    #[serde(deserialize_with = "null_vec")]
+    pub section: Vec<PgcSection>,
+    /// 同系列的其他季
+    #[serde(deserialize_with = "null_vec")]
+    pub seasons: Vec<PgcSeasonItem>,
+    pub rating: PgcRating,
+    pub stat: PgcStat,
+    pub rights: PgcRights,
+    pub payment: PgcPayment,
+    pub publish: PgcPublish,
+    pub up_info: PgcUpInfo,
+    pub new_ep: PgcNewEp,
+    pub series: PgcSeries,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PgcEpisode {
+    pub aid: i64,
+    pub badge: String,
+    pub badge_info: web::BadgeInfo,
+    pub badge_type: i64,
+    pub bvid: String,
+    pub cid: i64,
+    pub cover: String,
+    pub dimension: web::Dimension,
+    /// 时长 (毫秒)
+    pub duration: i64,
+    pub enable_vt: bool,
+    pub ep_id: i64,
+    pub from: String,
+    pub id: i64,
+    pub is_view_hide: bool,
+    pub link: String,
+    pub long_title: String,
+    pub pub_time: i64,
+    pub pv: i64,
+    pub release_date: String,
+    pub rights: web::EpRights,
+    pub share_copy: String,
+    pub share_url: String,
+    pub short_link: String,
+    pub skip: web::Skip,
+    pub status: i64,
+    pub subtitle: String,
+    pub title: String,
+    pub vid: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PgcSection {
+    pub id: i64,
+    pub title: String,
+    #[serde(rename = "type")]
+    pub section_type: i64,
+    #[serde(deserialize_with = "null_vec")]
+    pub episodes: Vec<PgcEpisode>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PgcSeasonItem {
+    pub season_id: i64,
+    pub media_id: i64,
+    pub season_title: String,
+    pub season_type: i64,
+    pub cover: String,
+    pub badge: String,
+    pub badge_type: i64,
+    pub enable_vt: bool,
+    pub stat: PgcSeasonItemStat,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PgcSeasonItemStat {
+    pub favorites: i64,
+    pub series_follow: i64,
+    pub views: i64,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PgcRating {
+    pub count: i64,
+    pub score: f64,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PgcStat {
+    pub coins: i64,
+    pub danmakus: i64,
+    pub favorite: i64,
+    pub favorites: i64,
+    pub likes: i64,
+    pub reply: i64,
+    pub share: i64,
+    pub views: i64,
+}
+
+/// 接口中的布尔值为0/1
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PgcRights {
+    pub allow_bp: i64,
+    pub allow_bp_rank: i64,
+    pub allow_download: i64,
+    pub allow_review: i64,
+    pub area_limit: i64,
+    pub can_watch: i64,
+    pub copyright: String,
+    pub is_cover_show: i64,
+    pub is_preview: i64,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PgcPayment {
+    pub price: String,
+    pub tip: String,
+    pub vip_promotion: String,
+    pub vip_first_promotion: String,
+    pub vip_discount: i64,
+    pub vip_price: String,
+    pub pay_type: PgcPayType,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PgcPayType {
+    pub allow_ticket: i64,
+    pub allow_vip_discount: i64,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PgcPublish {
+    pub is_finish: i64,
+    pub is_started: i64,
+    pub pub_time: String,
+    pub pub_time_show: String,
+    pub unknow_pub_date: i64,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PgcUpInfo {
+    pub mid: i64,
+    pub uname: String,
+    pub avatar: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PgcNewEp {
+    pub id: i64,
+    pub title: String,
+    pub desc: String,
+    pub is_new: i64,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PgcSeries {
+    pub series_id: i64,
+    pub series_title: String,
+}
+
+/// /pgc/review/user 的结果
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PgcReviewUser {
+    pub media: PgcMedia,
+}
+
+/// 番剧/电影 (PGC) 的条目信息, 可以通过media_id获取season_id
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PgcMedia {
+    pub media_id: i64,
+    pub season_id: i64,
+    pub title: String,
+    pub cover: String,
+    pub share_url: String,
+    #[serde(rename = "type")]
+    pub media_type: i64,
+    pub type_name: String,
+    pub areas: Vec<web::Area>,
+    pub rating: PgcRating,
+    pub new_ep: PgcNewEp,
+}
+
+impl PgcEpisode {
+    fn into_ep(self, section_type: i64, has_next: bool) -> web::Ep {
+        web::Ep {
+            aid: self.aid,
+            badge: self.badge,
+            badge_info: self.badge_info,
+            bvid: self.bvid,
+            cid: self.cid,
+            cover: self.cover,
+            dimension: self.dimension,
+            duration: self.duration,
+            enable_vt: self.enable_vt,
+            from: self.from,
+            id: self.id,
+            is_view_hide: self.is_view_hide,
+            link: self.link,
+            title_format: if self.title.parse::<f64>().is_ok() {
+                format!("第{}话", self.title)
+            } else {
+                self.title.clone()
+            },
+            long_title: self.long_title,
+            pub_time: self.pub_time,
+            pv: self.pv,
+            release_date: self.release_date,
+            rights: self.rights,
+            share_copy: self.share_copy,
+            share_url: self.share_url,
+            short_link: self.short_link,
+            has_skip: self.skip.op.is_some() || self.skip.ed.is_some(),
+            skip: self.skip,
+            status: self.status,
+            subtitle: self.subtitle,
+            title: self.title,
+            vid: self.vid,
+            loaded: true,
+            ep_id: if self.ep_id > 0 { self.ep_id } else { self.id },
+            section_type,
+            has_next,
+            ..Default::default()
+        }
+    }
+}
+
+fn into_eps(episodes: Vec<PgcEpisode>, section_type: i64) -> Vec<web::Ep> {
+    let len = episodes.len();
+    episodes
+        .into_iter()
+        .enumerate()
+        .map(|(index, ep)| ep.into_ep(section_type, index + 1 < len))
+        .collect()
+}
+
+/// 转换为与网页中相同结构的数据
+impl From<PgcSeason> for web::SsState {
+    fn from(season: PgcSeason) -> Self {
+        let episodes = into_eps(season.episodes, 0);
+        let sections: Vec<web::Section> = season
+            .section
+            .into_iter()
+            .map(|section| web::Section {
+                id: section.id,
+                title: section.title,
+                type_field: section.section_type,
+                ep_list: into_eps(section.episodes, section.section_type),
+            })
+            .collect();
+        let mut ep_map = std::collections::HashMap::new();
+        for ep in episodes
+            .iter()
+            .chain(sections.iter().flat_map(|section| section.ep_list.iter()))
+        {
+            ep_map.insert(ep.ep_id.to_string(), ep.clone());
+        }
+        let rights = web::SsMediaRights {
+            allow_bp: season.rights.allow_bp == 1,
+            allow_bp_rank: season.rights.allow_bp_rank == 1,
+            allow_review: season.rights.allow_review == 1,
+            is_preview: season.rights.is_preview == 1,
+            is_cover_show: season.rights.is_cover_show == 1,
+            can_watch: season.rights.can_watch == 1,
+            copyright: season.rights.copyright,
+            area_limit: season.rights.area_limit == 1,
+            allow_download: season.rights.allow_download,
+            ..Default::default()
+        };
+        web::SsState {
+            media_info: web::MediaInfo {
+                has_playable_ep: !episodes.is_empty(),
+                media_id: season.media_id,
+                season_id: season.season_id,
+                season_type: season.season_type,
+                stat: web::SsMediaStat {
+                    coins: season.stat.coins,
+                    danmakus: season.stat.danmakus,
+                    favorite: season.stat.favorite,
+                    favorites: season.stat.favorites,
+                    likes: season.stat.likes,
+                    reply: season.stat.reply,
+                    share: season.stat.share,
+                    views: season.stat.views,
+                },
+                alias: season.alias,
+                status: season.status,
+                record: season.record,
+                rating: web::SsMediaRating {
+                    count: season.rating.count,
+                    score: season.rating.score,
+                },
+                title: season.title,
+                episodes: episodes.clone(),
+                evaluate: season.evaluate,
+                jp_title: season.jp_title,
+                season_title: season.season_title,
+                areas: season.areas,
+                series: season.series.series_title,
+                square_cover: season.square_cover,
+                cover: season.cover,
+                publish: web::Publish {
+                    is_started: season.publish.is_started == 1,
+                    is_finish: season.publish.is_finish == 1,
+                    unknow_pub_date: season.publish.unknow_pub_date == 1,
+                    pub_time: season.publish.pub_time,
+                    pub_time_show: season.publish.pub_time_show,
+                },
+                rights: rights.clone(),
+                up_info: web::UpInfo {
+                    avatar: season.up_info.avatar,
+                    ..Default::default()
+                },
+                new_ep: web::NewEp {
+                    id: season.new_ep.id,
+                    title: serde_json::Value::String(season.new_ep.title),
+                    desc: season.new_ep.desc,
+                    is_new: season.new_ep.is_new == 1,
+                    ..Default::default()
+                },
+                styles: season.styles,
+                actors: season.actors,
+                staff: season.staff,
+                ..Default::default()
+            },
+            public_order_section_ids: sections.iter().map(|section| section.id).collect(),
+            sections_map: sections
+                .iter()
+                .map(|section| (section.id.to_string(), section.clone()))
+                .collect(),
+            rights,
+            payment: web::Payment {
+                price: season.payment.price,
+                tip: season.payment.tip,
+                vip_promotion: season.payment.vip_promotion,
+                vip_first_promotion: season.payment.vip_first_promotion,
+                vip_discount: season.payment.vip_discount,
+                pay_type: web::PayType {
+                    allow_ticket: season.payment.pay_type.allow_ticket == 1,
+                    allow_vip_discount: season.payment.pay_type.allow_vip_discount == 1,
+                },
+                vip_price: season.payment.vip_price,
+            },
+            ep_map,
+            init_ep_list: episodes,
+            init_sections: sections,
+            season_list: season
+                .seasons
+                .into_iter()
+                .map(|item| web::Season {
+                    season_id: item.season_id,
+                    season_title: item.season_title,
+                    season_type: item.season_type,
+                    cover: item.cover,
+                    badge_type: item.badge_type,
+                    badge: item.badge,
+                    views: item.stat.views,
+                    series_follow: item.stat.series_follow,
+                    enable_vt: item.enable_vt,
+                    ..Default::default()
+                })
+                .collect(),
+        }
+    }
+}
+
 pub mod web {
     use crate::entities::default_option;
     use crate::entities::default_string;
@@ -1032,18 +1835,103 @@ pub const VIDEO_QUALITY_4K: VideoQuality = VideoQuality { code: 120 };
 // 大多情况需求认证大会员账号
 pub const VIDEO_QUALITY_HDR: VideoQuality = VideoQuality { code: 125 };
 
-// 视频质量
-#[derive(Default, Debug, Clone, PartialEq)]
+// 杜比视界	仅支持dash方式
+// 需要fnver&512=512
+// 大多情况需求认证大会员账号
+pub const VIDEO_QUALITY_DOLBY_VISION: VideoQuality = VideoQuality { code: 126 };
+
+// 8K 超高清	仅支持dash方式
+// 需要fnver&1024=1024且fourk=1
+// 大多情况需求认证大会员账号
+pub const VIDEO_QUALITY_8K: VideoQuality = VideoQuality { code: 127 };
+
+// 视频质量, 按code排序即为画质的高低
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct VideoQuality {
     pub code: i64,
 }
 
+impl VideoQuality {
+    pub fn from_code(code: i64) -> Self {
+        VideoQuality { code }
+    }
+
+    /// 名称, 未知的画质返回空字符串
+    pub fn label(&self) -> &'static str {
+        match self.code {
+            6 => "240P 极速",
+            16 => "360P 流畅",
+            32 => "480P 清晰",
+            64 => "720P 高清",
+            74 => "720P60 高帧率",
+            80 => "1080P 高清",
+            112 => "1080P+ 高码率",
+            116 => "1080P60 高帧率",
+            120 => "4K 超清",
+            125 => "HDR 真彩色",
+            126 => "杜比视界",
+            127 => "8K 超高清",
+            _ => "",
+        }
+    }
+
+    /// 是否需要登录
+    pub fn need_login(&self) -> bool {
+        self.code > VIDEO_QUALITY_720P.code
+    }
+
+    /// 是否 (大多情况下) 需要大会员
+    pub fn need_vip(&self) -> bool {
+        self.code >= VIDEO_QUALITY_1080P_HIGH.code
+    }
+
+    /// 取流时需要的FNVAL_*标志, 0表示没有额外要求
+    pub fn fnval(&self) -> i64 {
+        match self.code {
+            6 => FNVAL_MP4,
+            120 => FNVAL_DASH | FNVAL_DASH_4K,
+            125 => FNVAL_DASH | FNVAL_DASH_HDR,
+            126 => FNVAL_DASH | FNVAL_DASH_VISION,
+            127 => FNVAL_DASH | FNVAL_DASH_8K,
+            _ => FNVAL_FLV,
+        }
+    }
+
+    /// 是否需要fourk=1
+    pub fn need_fourk(&self) -> bool {
+        self.code == VIDEO_QUALITY_4K.code || self.code == VIDEO_QUALITY_8K.code
+    }
+}
+
+impl std::fmt::Display for VideoQuality {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.label() {
+            "" => write!(f, "{}", self.code),
+            label => write!(f, "{}", label),
+        }
+    }
+}
+
+enum_code!(VideoCodec {
+    Avc(7, "AVC/H.264"),
+    Hevc(12, "HEVC/H.265"),
+    Av1(13, "AV1"),
+});
+
+enum_code!(AudioQuality {
+    Q64K(30216, "64K"),
+    Q132K(30232, "132K"),
+    Q192K(30280, "192K"),
+    Dolby(30250, "杜比全景声"),
+    HiRes(30251, "Hi-Res无损"),
+});
+
 impl Serialize for VideoQuality {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        serializer.serialize_i64(self.code.clone())
+        serializer.serialize_i64(self.code)
     }
 }
 
@@ -1098,6 +1986,57 @@ pub struct LoginTvQrInfo {
     pub refresh_token: String,
     #[serde(default = "default_i64")]
     pub expires_in: i64,
+    /// 登录成功时返回的WEB Cookie
+    #[serde(default = "default_option")]
+    pub cookie_info: Option<AppCookieInfo>,
+}
+
+/// APP/TV登录返回的WEB Cookie
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AppCookieInfo {
+    #[serde(default = "default_vec", deserialize_with = "null_vec")]
+    pub cookies: Vec<AppCookie>,
+    #[serde(default = "default_vec", deserialize_with = "null_vec")]
+    pub domains: Vec<String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AppCookie {
+    #[serde(default = "default_string")]
+    pub name: String,
+    #[serde(default = "default_string")]
+    pub value: String,
+    #[serde(default = "default_i64")]
+    pub http_only: i64,
+    /// 过期时间 (秒级时间戳)
+    #[serde(default = "default_i64")]
+    pub expires: i64,
+    #[serde(default = "default_i64")]
+    pub secure: i64,
+}
+
+/// APP/TV登录的令牌信息
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AppTokenInfo {
+    #[serde(default = "default_i64")]
+    pub mid: i64,
+    #[serde(default = "default_string")]
+    pub access_token: String,
+    #[serde(default = "default_string")]
+    pub refresh_token: String,
+    #[serde(default = "default_i64")]
+    pub expires_in: i64,
+}
+
+/// 刷新APP/TV令牌的结果
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AppTokenRefresh {
+    #[serde(default = "default_i64")]
+    pub status: i64,
+    #[serde(default = "Default::default")]
+    pub token_info: AppTokenInfo,
+    #[serde(default = "default_option")]
+    pub cookie_info: Option<AppCookieInfo>,
 }
 
 /////////
@@ -1300,7 +2239,9 @@ pub struct CollectionDetailMeta {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PageInfo {
+    #[serde(alias = "num")]
     pub page_num: i64,
+    #[serde(alias = "size")]
     pub page_size: i64,
     pub total: i64,
 }
@@ -1359,6 +2300,15 @@ pub struct SeriesVideoInfoData {
     pub recent_aids: Vec<i64>,
 }
 
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SeriesArchivesPage {
+    #[serde(default = "default_vec", deserialize_with = "null_vec")]
+    pub aids: Vec<i64>,
+    #[serde(default = "default_vec", deserialize_with = "null_vec")]
+    pub archives: Vec<VideoArchive>,
+    pub page: PageInfo,
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SeriesVideoInfoMeta {
     pub series_id: i64,
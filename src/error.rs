@@ -0,0 +1,137 @@
+/// 错误码: 账号未登录
+pub const CODE_NOT_LOGIN: i64 = -101;
+/// 错误码: 请求被风控拦截
+pub const CODE_RISK_CONTROL: i64 = -352;
+/// 错误码: 请求被拦截 (多见于频率过高或UA异常)
+pub const CODE_REQUEST_BLOCKED: i64 = -412;
+/// 错误码: 啥都木有 (视频不存在或已删除)
+pub const CODE_NOT_FOUND: i64 = -404;
+
+/// 错误
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// 账号未登录 (-101)
+    #[error("账号未登录 ({code}): {message}")]
+    NotLogin {
+        code: i64,
+        message: String,
+        ttl: i64,
+    },
+
+    /// 被风控拦截 (-352 / -412), 需要降低请求频率或补全Cookie/签名
+    #[error("请求被风控拦截 ({code}): {message}")]
+    RiskControl {
+        code: i64,
+        message: String,
+        ttl: i64,
+    },
+
+    /// 资源不存在 (-404)
+    #[error("资源不存在 ({code}): {message}")]
+    NotFound {
+        code: i64,
+        message: String,
+        ttl: i64,
+    },
+
+    /// 服务器返回的其他错误码
+    #[error("服务器返回错误 ({code}): {message}")]
+    Api {
+        code: i64,
+        message: String,
+        ttl: i64,
+    },
+
+    /// 网络请求失败
+    #[error("网络请求失败: {0}")]
+    Transport(#[from] reqwest::Error),
+
+    /// 文件读写失败
+    #[error("文件读写失败: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// 返回内容无法解析, path为出错的位置
+    #[error("返回内容解析失败 ({path}): {source}")]
+    Decode {
+        path: String,
+        source: serde_json::Error,
+    },
+
+    /// 返回内容格式错误
+    #[error("返回内容格式错误: {0}")]
+    Format(String),
+
+    /// 等待超时
+    #[error("等待超时")]
+    Timeout,
+
+    /// 参数错误
+    #[error("参数错误: {0}")]
+    InvalidArgument(String),
+}
+
+impl Error {
+    /// 根据B站返回的code构造错误
+    pub fn from_code(code: i64, message: impl Into<String>, ttl: i64) -> Self {
+        let message = message.into();
+        match code {
+            CODE_NOT_LOGIN => Error::NotLogin { code, message, ttl },
+            CODE_RISK_CONTROL | CODE_REQUEST_BLOCKED => Error::RiskControl { code, message, ttl },
+            CODE_NOT_FOUND => Error::NotFound { code, message, ttl },
+            _ => Error::Api { code, message, ttl },
+        }
+    }
+
+    /// B站返回的code, 非服务器返回的错误为None
+    pub fn code(&self) -> Option<i64> {
+        match self {
+            Error::NotLogin { code, .. }
+            | Error::RiskControl { code, .. }
+            | Error::NotFound { code, .. }
+            | Error::Api { code, .. } => Some(*code),
+            _ => None,
+        }
+    }
+
+    /// B站返回的message
+    pub fn message(&self) -> Option<&str> {
+        match self {
+            Error::NotLogin { message, .. }
+            | Error::RiskControl { message, .. }
+            | Error::NotFound { message, .. }
+            | Error::Api { message, .. } => Some(message.as_str()),
+            _ => None,
+        }
+    }
+
+    /// B站返回的ttl
+    pub fn ttl(&self) -> Option<i64> {
+        match self {
+            Error::NotLogin { ttl, .. }
+            | Error::RiskControl { ttl, .. }
+            | Error::NotFound { ttl, .. }
+            | Error::Api { ttl, .. } => Some(*ttl),
+            _ => None,
+        }
+    }
+}
+
+impl From<serde_path_to_error::Error<serde_json::Error>> for Error {
+    fn from(err: serde_path_to_error::Error<serde_json::Error>) -> Self {
+        Error::Decode {
+            path: err.path().to_string(),
+            source: err.into_inner(),
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Error::Decode {
+            path: ".".to_string(),
+            source: err,
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
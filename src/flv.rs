@@ -0,0 +1,277 @@
+//! 合并分段的FLV
+//!
+//! FLV方式取流时一个视频可能被分为多段 (VideoUrl.durl), 每段的时间戳都从0开始
+//! 合并时依次平移时间戳, 只保留第一段的onMetaData与音视频序列头, 并修正onMetaData中的duration
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::{Downloader, Durl, Error, Result};
+
+const TAG_AUDIO: u8 = 8;
+const TAG_VIDEO: u8 = 9;
+const TAG_SCRIPT: u8 = 18;
+
+/// FLV Tag
+struct Tag {
+    tag_type: u8,
+    timestamp: u32,
+    data: Vec<u8>,
+}
+
+impl Tag {
+    /// 是否为AVC/HEVC或AAC的序列头
+    fn is_sequence_header(&self) -> bool {
+        match self.tag_type {
+            // CodecID 7: AVC, 12: HEVC; AVCPacketType 0: sequence header
+            TAG_VIDEO => {
+                self.data.len() > 1 && matches!(self.data[0] & 0x0F, 7 | 12) && self.data[1] == 0
+            }
+            // SoundFormat 10: AAC; AACPacketType 0: sequence header
+            TAG_AUDIO => self.data.len() > 1 && self.data[0] >> 4 == 10 && self.data[1] == 0,
+            _ => false,
+        }
+    }
+}
+
+struct FlvReader<R> {
+    reader: R,
+    header: Vec<u8>,
+}
+
+impl<R: Read> FlvReader<R> {
+    fn new(mut reader: R) -> Result<Self> {
+        let mut header = [0u8; 9];
+        reader.read_exact(&mut header)?;
+        if &header[0..3] != b"FLV" {
+            return Err(Error::Format("不是FLV文件".to_string()));
+        }
+        let header_len = u32::from_be_bytes(header[5..9].try_into().unwrap()) as usize;
+        let mut header = header.to_vec();
+        if header_len > 9 {
+            let mut rest = vec![0u8; header_len - 9];
+            reader.read_exact(&mut rest)?;
+            header.extend(rest);
+        }
+        // PreviousTagSize0
+        let mut previous = [0u8; 4];
+        reader.read_exact(&mut previous)?;
+        Ok(Self { reader, header })
+    }
+
+    fn next_tag(&mut self) -> Result<Option<Tag>> {
+        let mut head = [0u8; 11];
+        match self.reader.read_exact(&mut head) {
+            Ok(()) => (),
+            // 文件末尾 (包括被截断的最后一个Tag)
+            Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err.into()),
+        }
+        let size = u32::from_be_bytes([0, head[1], head[2], head[3]]) as usize;
+        let timestamp = u32::from_be_bytes([head[7], head[4], head[5], head[6]]);
+        let mut data = vec![0u8; size];
+        let mut previous = [0u8; 4];
+        if self.reader.read_exact(&mut data).is_err()
+            || self.reader.read_exact(&mut previous).is_err()
+        {
+            return Ok(None);
+        }
+        Ok(Some(Tag {
+            tag_type: head[0] & 0x1F,
+            timestamp,
+            data,
+        }))
+    }
+}
+
+fn write_tag(writer: &mut impl Write, tag: &Tag, timestamp: u32) -> Result<()> {
+    let size = (tag.data.len() as u32).to_be_bytes();
+    let ts = timestamp.to_be_bytes();
+    writer.write_all(&[
+        tag.tag_type,
+        size[1],
+        size[2],
+        size[3],
+        ts[1],
+        ts[2],
+        ts[3],
+        ts[0],
+        0,
+        0,
+        0,
+    ])?;
+    writer.write_all(&tag.data)?;
+    writer.write_all(&(tag.data.len() as u32 + 11).to_be_bytes())?;
+    Ok(())
+}
+
+/// 合并多个FLV分段, 返回合并后的时长 (毫秒)
+/// 为同步IO, 在异步环境中请使用spawn_blocking
+pub fn concat_flv(inputs: &[impl AsRef<Path>], output: impl AsRef<Path>) -> Result<u32> {
+    if inputs.is_empty() {
+        return Err(Error::InvalidArgument("没有需要合并的分段".to_string()));
+    }
+    let mut writer = BufWriter::new(File::create(output.as_ref())?);
+    let mut written = 0u64;
+    // onMetaData中duration数值在输出文件中的位置
+    let mut duration_offset = None;
+    let mut offset = 0u32;
+    let mut end = 0u32;
+    for (index, input) in inputs.iter().enumerate() {
+        let mut reader = FlvReader::new(BufReader::new(File::open(input.as_ref())?))?;
+        if index == 0 {
+            writer.write_all(&reader.header)?;
+            writer.write_all(&0u32.to_be_bytes())?;
+            written += reader.header.len() as u64 + 4;
+        }
+        let mut last = [None::<u32>; 2];
+        let mut delta = 0u32;
+        let mut segment_end = 0u32;
+        while let Some(tag) = reader.next_tag()? {
+            if index > 0 && (tag.tag_type == TAG_SCRIPT || tag.is_sequence_header()) {
+                continue;
+            }
+            if tag.tag_type == TAG_SCRIPT && duration_offset.is_none() {
+                duration_offset =
+                    amf_duration_offset(&tag.data).map(|pos| written + 11 + pos as u64);
+            }
+            // 记录同类Tag的间隔, 作为分段之间的间隔
+            if tag.tag_type == TAG_AUDIO || tag.tag_type == TAG_VIDEO {
+                let slot = (tag.tag_type == TAG_VIDEO) as usize;
+                if let Some(previous) = last[slot] {
+                    if tag.timestamp > previous {
+                        delta = tag.timestamp - previous;
+                    }
+                }
+                last[slot] = Some(tag.timestamp);
+                segment_end = segment_end.max(tag.timestamp);
+            }
+            let timestamp = add_timestamp(offset, tag.timestamp)?;
+            write_tag(&mut writer, &tag, timestamp)?;
+            written += tag.data.len() as u64 + 15;
+            end = end.max(add_timestamp(timestamp, delta)?);
+        }
+        offset = add_timestamp(offset, add_timestamp(segment_end, delta)?)?;
+    }
+    let mut file = writer.into_inner().map_err(|err| err.into_error())?;
+    if let Some(duration_offset) = duration_offset {
+        file.seek(SeekFrom::Start(duration_offset))?;
+        file.write_all(&(end as f64 / 1000.0).to_be_bytes())?;
+    }
+    file.sync_all()?;
+    Ok(end)
+}
+
+/// 时间戳相加, 超出FLV能表示的范围时返回错误
+fn add_timestamp(a: u32, b: u32) -> Result<u32> {
+    a.checked_add(b)
+        .ok_or_else(|| Error::Format(format!("时间戳溢出: {} + {}", a, b)))
+}
+
+/// 查找onMetaData中duration数值 (AMF0 Number) 的位置
+fn amf_duration_offset(data: &[u8]) -> Option<usize> {
+    // 第一个值为字符串 "onMetaData"
+    if data.first() != Some(&2) {
+        return None;
+    }
+    let name_len = u16::from_be_bytes([*data.get(1)?, *data.get(2)?]) as usize;
+    if data.get(3..3 + name_len)? != b"onMetaData" {
+        return None;
+    }
+    let mut pos = 3 + name_len;
+    match *data.get(pos)? {
+        // ECMA Array
+        8 => pos += 5,
+        // Object
+        3 => pos += 1,
+        _ => return None,
+    }
+    loop {
+        let key_len = u16::from_be_bytes([*data.get(pos)?, *data.get(pos + 1)?]) as usize;
+        let key = data.get(pos + 2..pos + 2 + key_len)?;
+        pos += 2 + key_len;
+        if key_len == 0 && *data.get(pos)? == 9 {
+            return None;
+        }
+        if key == b"duration" && *data.get(pos)? == 0 {
+            return Some(pos + 1);
+        }
+        pos = amf_skip(data, pos)?;
+    }
+}
+
+/// 跳过一个AMF0值, 返回下一个值的位置
+fn amf_skip(data: &[u8], pos: usize) -> Option<usize> {
+    let read_u16 =
+        |pos: usize| Some(u16::from_be_bytes([*data.get(pos)?, *data.get(pos + 1)?]) as usize);
+    let read_u32 =
+        |pos: usize| Some(u32::from_be_bytes(data.get(pos..pos + 4)?.try_into().ok()?) as usize);
+    match *data.get(pos)? {
+        // Number
+        0 => Some(pos + 9),
+        // Boolean
+        1 => Some(pos + 2),
+        // String
+        2 => Some(pos + 3 + read_u16(pos + 1)?),
+        // Object / ECMA Array
+        3 | 8 => {
+            let mut pos = if data[pos] == 8 { pos + 5 } else { pos + 1 };
+            loop {
+                let key_len = read_u16(pos)?;
+                pos += 2 + key_len;
+                if key_len == 0 && *data.get(pos)? == 9 {
+                    return Some(pos + 1);
+                }
+                pos = amf_skip(data, pos)?;
+            }
+        }
+        // Null / Undefined
+        5 | 6 => Some(pos + 1),
+        // Strict Array
+        10 => {
+            let count = read_u32(pos + 1)?;
+            let mut pos = pos + 5;
+            for _ in 0..count {
+                pos = amf_skip(data, pos)?;
+            }
+            Some(pos)
+        }
+        // Date
+        11 => Some(pos + 11),
+        // Long String
+        12 => Some(pos + 5 + read_u32(pos + 1)?),
+        _ => None,
+    }
+}
+
+/// 下载分段的FLV
+impl Downloader {
+    /// 按order下载所有分段并合并为一个FLV, 返回合并后的时长 (毫秒)
+    /// 分段临时保存为 {path}.{order}.flv, 合并完成后删除
+    pub async fn download_flv(&self, durls: &[Durl], path: impl AsRef<Path>) -> Result<u32> {
+        if durls.is_empty() {
+            return Err(Error::InvalidArgument("没有可用的下载地址".to_string()));
+        }
+        let path = path.as_ref();
+        let mut durls: Vec<&Durl> = durls.iter().collect();
+        durls.sort_by_key(|durl| durl.order);
+        let mut parts: Vec<PathBuf> = vec![];
+        for durl in durls {
+            let mut part = path.as_os_str().to_owned();
+            part.push(format!(".{}.flv", durl.order));
+            let part = PathBuf::from(part);
+            self.download(durl, &part).await?;
+            parts.push(part);
+        }
+        let output = path.to_path_buf();
+        let concat_parts = parts.clone();
+        let duration = tokio::task::spawn_blocking(move || concat_flv(&concat_parts, output))
+            .await
+            .map_err(std::io::Error::other)??;
+        for part in parts {
+            tokio::fs::remove_file(part).await?;
+        }
+        Ok(duration)
+    }
+}
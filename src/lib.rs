@@ -1,50 +1,241 @@
 use std::str::FromStr;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
 
-use anyhow::anyhow;
-pub use anyhow::{Error, Result};
-use chrono::Timelike;
 use tracing::debug;
 
+pub use credential::*;
+pub use download::*;
 pub use entities::*;
+pub use error::*;
+pub use flv::*;
+pub use login::*;
+#[cfg(feature = "mux")]
+pub use mux::*;
+pub use paging::*;
+pub use playurl::*;
+#[cfg(feature = "qr")]
+pub use qr::*;
+pub use selector::*;
+pub use session::*;
 pub use utils::*;
 
+pub mod credential;
+pub mod download;
 pub mod entities;
+pub mod error;
+pub mod flv;
+pub mod login;
+pub mod mpd;
+#[cfg(feature = "mux")]
+pub mod mux;
+pub mod paging;
+pub mod playurl;
+#[cfg(feature = "qr")]
+pub mod qr;
+pub mod selector;
+pub mod session;
 pub mod utils;
 
-const API_HOST_URL: &'static str = "https://api.bilibili.com";
-const PASSPORT_HOST_URL: &'static str = "https://passport.bilibili.com";
+const API_HOST_URL: &str = "https://api.bilibili.com";
+const PASSPORT_HOST_URL: &str = "https://passport.bilibili.com";
+const WWW_HOST_URL: &str = "https://www.bilibili.com";
 
-const APP_KEY_TV: &'static str = "4409e2ce8ffd12b8";
-const APP_SEC_TV: &'static str = "59b43e04ad6965f34319062b478f83dd";
-const LOCAL_ID_TV: &'static str = "0";
+const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/106.0.0.0 Safari/537.36";
+
+pub(crate) const APP_KEY_TV: &str = "4409e2ce8ffd12b8";
+pub(crate) const APP_SEC_TV: &str = "59b43e04ad6965f34319062b478f83dd";
+pub(crate) const LOCAL_ID_TV: &str = "0";
 
 /// 客户端
+/// clone得到的客户端共享同一个会话
 #[derive(Clone)]
 pub struct Client {
     agent: reqwest::Client,
-    sess_data: Option<String>,
+    api_host: String,
+    passport_host: String,
+    www_host: String,
+    session: Arc<RwLock<Session>>,
+    wbi_keys: Arc<Mutex<Option<WbiKeys>>>,
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 客户端构造器
+pub struct ClientBuilder {
+    api_host: String,
+    passport_host: String,
+    www_host: String,
+    user_agent: String,
+    connect_timeout: Option<Duration>,
+    timeout: Option<Duration>,
+    proxy: Option<String>,
+    default_headers: reqwest::header::HeaderMap,
+    agent: Option<reqwest::Client>,
+}
+
+impl Default for ClientBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 客户端构造器
+impl ClientBuilder {
+    /// 构造方法, 默认连接B站的服务器
+    pub fn new() -> Self {
+        Self {
+            api_host: API_HOST_URL.to_string(),
+            passport_host: PASSPORT_HOST_URL.to_string(),
+            www_host: WWW_HOST_URL.to_string(),
+            user_agent: USER_AGENT.to_string(),
+            connect_timeout: None,
+            timeout: None,
+            proxy: None,
+            default_headers: reqwest::header::HeaderMap::new(),
+            agent: None,
+        }
+    }
+
+    /// API服务器地址, 默认 https://api.bilibili.com
+    pub fn api_host(mut self, host: impl Into<String>) -> Self {
+        self.api_host = host.into();
+        self
+    }
+
+    /// PASSPORT服务器地址, 默认 https://passport.bilibili.com
+    pub fn passport_host(mut self, host: impl Into<String>) -> Self {
+        self.passport_host = host.into();
+        self
+    }
+
+    /// WEB页面地址, 默认 https://www.bilibili.com
+    pub fn www_host(mut self, host: impl Into<String>) -> Self {
+        self.www_host = host.into();
+        self
+    }
+
+    /// User-Agent
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    /// 连接超时
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// 请求超时, 从连接开始到读取完响应结束
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// 代理, 例如 http://127.0.0.1:7890 或 socks5://127.0.0.1:1080 (socks需要开启socks特性)
+    pub fn proxy(mut self, proxy: impl Into<String>) -> Self {
+        self.proxy = Some(proxy.into());
+        self
+    }
+
+    /// 每个请求都携带的请求头
+    pub fn default_headers(mut self, headers: reqwest::header::HeaderMap) -> Self {
+        self.default_headers = headers;
+        self
+    }
+
+    /// 使用已经构造好的reqwest客户端, 此时User-Agent/超时/代理/请求头的设置不再生效
+    pub fn agent(mut self, agent: reqwest::Client) -> Self {
+        self.agent = Some(agent);
+        self
+    }
+
+    /// 构造客户端
+    pub fn build(self) -> Result<Client> {
+        let agent = match self.agent {
+            Some(agent) => agent,
+            None => {
+                let mut builder = reqwest::ClientBuilder::new()
+                    .user_agent(self.user_agent)
+                    .default_headers(self.default_headers);
+                if let Some(timeout) = self.connect_timeout {
+                    builder = builder.connect_timeout(timeout);
+                }
+                if let Some(timeout) = self.timeout {
+                    builder = builder.timeout(timeout);
+                }
+                if let Some(proxy) = self.proxy {
+                    builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+                }
+                builder.build()?
+            }
+        };
+        Ok(Client {
+            agent,
+            api_host: self.api_host.trim_end_matches('/').to_string(),
+            passport_host: self.passport_host.trim_end_matches('/').to_string(),
+            www_host: self.www_host.trim_end_matches('/').to_string(),
+            session: Arc::new(RwLock::new(Session::new())),
+            wbi_keys: Arc::new(Mutex::new(None)),
+        })
+    }
 }
 
 /// 客户端
 impl Client {
     /// 构造方法
     pub fn new() -> Self {
-        Self {
-            agent: reqwest::ClientBuilder::new()
-                .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/106.0.0.0 Safari/537.36")
-                .build().unwrap(),
-            sess_data: None,
-        }
+        ClientBuilder::new().build().unwrap()
+    }
+
+    /// 客户端构造器
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::new()
     }
 
     /// 对请求增加身份认证Cookie
     fn sess(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
-        match &self.sess_data {
-            Some(web_token) => request.header("Cookie", format!("SESSDATA={}", web_token,)),
+        match self.session.read().unwrap().cookie_header() {
+            Some(cookie) => request.header(reqwest::header::COOKIE, cookie),
             None => request,
         }
     }
 
+    /// 将响应中的Set-Cookie保存到会话
+    fn capture_cookies(&self, resp: &reqwest::Response) {
+        if resp.headers().contains_key(reqwest::header::SET_COOKIE) {
+            self.session
+                .write()
+                .unwrap()
+                .update_from_headers(resp.headers());
+        }
+    }
+
+    /// 当前会话
+    pub fn session(&self) -> Session {
+        self.session.read().unwrap().clone()
+    }
+
+    /// 替换当前会话
+    pub fn set_session(&mut self, session: Session) {
+        *self.session.write().unwrap() = session;
+    }
+
+    /// 当前登录的token, 未登录时为None
+    pub fn web_token(&self) -> Option<WebToken> {
+        self.session.read().unwrap().web_token()
+    }
+
+    /// CSRF Token (bili_jct), 写操作需要携带
+    pub fn csrf(&self) -> Option<String> {
+        self.session.read().unwrap().csrf().map(str::to_string)
+    }
+
     /// 请求并获得结果
     pub async fn request_api<T: for<'de> serde::Deserialize<'de>>(
         &self,
@@ -53,7 +244,98 @@ impl Client {
         query: Option<serde_json::Value>,
         body: Option<serde_json::Value>,
     ) -> Result<T> {
-        let url = format!("{}{}", API_HOST_URL, path);
+        let response: Response<T> = self.request_response(method, path, query, body).await?;
+        match response.code {
+            0 => response
+                .data
+                .ok_or_else(|| Error::Format("返回内容为空".to_string())),
+            _ => Err(Error::from_code(
+                response.code,
+                response.message,
+                response.ttl,
+            )),
+        }
+    }
+
+    /// 请求并获得结果, 对query进行WBI签名
+    pub async fn request_api_wbi<T: for<'de> serde::Deserialize<'de>>(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        query: Option<serde_json::Value>,
+        body: Option<serde_json::Value>,
+    ) -> Result<T> {
+        let query = self
+            .wbi_keys()
+            .await?
+            .sign(query.unwrap_or(serde_json::Value::Null))?;
+        self.request_api(method, path, Some(query), body).await
+    }
+
+    /// 获取WBI签名密钥, 密钥会缓存到当天结束
+    pub async fn wbi_keys(&self) -> Result<WbiKeys> {
+        if let Some(keys) = self.wbi_keys.lock().unwrap().as_ref() {
+            if !keys.expired() {
+                return Ok(keys.clone());
+            }
+        }
+        let nav = self.nav().await?;
+        WbiKeys::from_wbi_img(&nav.wbi_img)
+    }
+
+    /// 导航栏用户信息, 可用于检查登录状态是否有效 (未登录时is_login为false, 不会返回错误)
+    /// 返回的wbi_img会更新到WBI签名密钥的缓存
+    pub async fn nav(&self) -> Result<Nav> {
+        // 未登录时code为-101, 但是仍然会返回data
+        let response: Response<Nav> = self
+            .request_response(reqwest::Method::GET, "/x/web-interface/nav", None, None)
+            .await?;
+        let nav = response
+            .data
+            .ok_or_else(|| Error::from_code(response.code, response.message, response.ttl))?;
+        if let Ok(keys) = WbiKeys::from_wbi_img(&nav.wbi_img) {
+            *self.wbi_keys.lock().unwrap() = Some(keys);
+        }
+        Ok(nav)
+    }
+
+    /// 使用TV登录的令牌请求APP接口, query会加入access_key/appkey/ts并进行APP签名
+    pub async fn request_app_api<T: for<'de> serde::Deserialize<'de>>(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        query: Option<serde_json::Value>,
+    ) -> Result<T> {
+        let access_key = self
+            .session()
+            .tv_token()
+            .ok_or_else(|| Error::InvalidArgument("缺少TV登录的令牌".to_string()))?
+            .access_token
+            .clone();
+        let mut query = match query {
+            Some(serde_json::Value::Object(obj)) => obj,
+            None => serde_json::Map::new(),
+            _ => return Err(Error::InvalidArgument("sign error: not object".to_string())),
+        };
+        query.insert("access_key".to_string(), access_key.into());
+        query.insert("appkey".to_string(), APP_KEY_TV.into());
+        query.insert(
+            "ts".to_string(),
+            chrono::Local::now().timestamp().to_string().into(),
+        );
+        let query = sign_form(serde_json::Value::Object(query), APP_SEC_TV)?;
+        self.request_api(method, path, Some(query), None).await
+    }
+
+    /// 请求并获得未经处理的结果, 不检查code
+    async fn request_response<T: for<'de> serde::Deserialize<'de>>(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        query: Option<serde_json::Value>,
+        body: Option<serde_json::Value>,
+    ) -> Result<Response<T>> {
+        let url = format!("{}{}", self.api_host, path);
         debug!(target = "network", "Request : {}", url);
         let request = self.agent.request(method, url.as_str());
         let request = self.sess(request);
@@ -66,13 +348,10 @@ impl Client {
             Some(body) => request.body(serde_json::to_string(&body)?).send(),
         };
         let resp = resp.await?;
+        self.capture_cookies(&resp);
         let body = resp.text().await?;
         debug!(target = "network", "Response : {}", body);
-        let response: Response<T> = from_str(&body)?;
-        match &(response.code) {
-            0 => Ok(response.data.ok_or(Error::msg("返回内容为空"))?),
-            _ => Err(Error::msg(response.message)),
-        }
+        from_str(&body)
     }
 
     /// 请求PASSPORT服务器并获得结果
@@ -84,9 +363,10 @@ impl Client {
         query: Option<serde_json::Value>,
         body: Option<serde_json::Value>,
     ) -> Result<serde_json::Value> {
-        let url = format!("{}{}", PASSPORT_HOST_URL, path);
+        let url = format!("{}{}", self.passport_host, path);
         debug!(target = "network", "Request : {}", url);
         let request = self.agent.request(method, url.as_str());
+        let request = self.sess(request);
         let request = match query {
             None => request,
             Some(query) => request.query(&query),
@@ -96,23 +376,36 @@ impl Client {
             Some(body) => request.form(&body).send(),
         };
         let resp = resp.await?;
+        self.capture_cookies(&resp);
         let body = resp.text().await?;
         debug!(target = "network", "Response : {}", body);
         let json: serde_json::Value = from_str(body.as_str())?;
         Ok(json)
     }
 
+    /// 请求PASSPORT服务器, code为0时返回data
+    pub async fn request_passport_data<T: for<'de> serde::Deserialize<'de>>(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        query: Option<serde_json::Value>,
+        body: Option<serde_json::Value>,
+    ) -> Result<T> {
+        let json = self.request_passport(method, path, query, body).await?;
+        check_code(&json)?;
+        let data = json
+            .get("data")
+            .ok_or_else(|| Error::Format("服务器的响应中不包含data".to_string()))?;
+        from_value(data.clone())
+    }
+
     /// 请求web页面
     pub async fn request_web(&self, url: String) -> Result<String> {
         debug!(target = "network", "Request : {}", url);
         let req = self.agent.get(url);
-        let rsp = self
-            .sess(req)
-            .send()
-            .await?
-            .error_for_status()?
-            .text()
-            .await?;
+        let rsp = self.sess(req).send().await?;
+        self.capture_cookies(&rsp);
+        let rsp = rsp.error_for_status()?.text().await?;
         debug!(target = "network", "Response : {}", rsp);
         Ok(rsp)
     }
@@ -120,29 +413,15 @@ impl Client {
     /// WEB二维码登录 - 申请二维码
     /// 此返回结构略有不同, 所以进行了自定义封装
     /// code为0成功
+    /// 旧接口, 建议使用 login_qr_generate
     pub async fn login_qr(&self) -> Result<LoginQrData> {
-        let json = self
-            .request_passport(reqwest::Method::GET, "/qrcode/getLoginUrl", None, None)
-            .await?;
-        let code = json
-            .get("code")
-            .ok_or(Error::msg("返回内容格式错误: 服务器的响应中不包含code"))?;
-        if !code.is_i64() {
-            return Err(Error::msg("返回内容格式错误: code应为数字类型"));
-        }
-        if code.as_i64().unwrap() != 0 {
-            return Err(Error::msg(format!(
-                "未能获取到二维码(1)，服务器返回的code为: {}",
-                code.as_i64().unwrap()
-            )));
-        }
-        let data = json
-            .get("data")
-            .ok_or(Error::msg("返回内容格式错误: 服务器的响应中不包含data"))?;
-        Ok(serde_json::from_value(data.clone())?)
+        self.request_passport_data(reqwest::Method::GET, "/qrcode/getLoginUrl", None, None)
+            .await
     }
 
     /// WEB二维码登录 - 获取Web二维码登录信息
+    /// 登录成功时token会保存到会话, url中的Expires (有效秒数) 换算为过期时间戳
+    /// 旧接口, 建议使用 login_qr_poll
     pub async fn login_qr_info(&self, oauth_key: String) -> Result<LoginQrInfo> {
         let json = self
             .request_passport(
@@ -154,13 +433,10 @@ impl Client {
             .await?;
         let value = json
             .get("data")
-            .ok_or(Error::msg("返回内容格式错误: 服务器的响应中不包含data"))?;
-        if value.is_i64() {
+            .ok_or_else(|| Error::Format("服务器的响应中不包含data".to_string()))?;
+        if let Some(error_data) = value.as_i64() {
             Ok(LoginQrInfo {
-                error_data: value.as_i64().ok_or(Error::msg(format!(
-                    "未能获取到二维码(2)，服务器返回的code为: {}",
-                    value.as_i64().unwrap()
-                )))?,
+                error_data,
                 url: String::default(),
             })
         } else {
@@ -168,114 +444,130 @@ impl Client {
                 error_data: 0,
                 url: value["url"]
                     .as_str()
-                    .ok_or(Error::msg("error format"))?
+                    .ok_or_else(|| Error::Format("data中不包含url".to_string()))?
                     .to_string(),
             };
+            if let Ok(mut token) = parse_cross_domain_token(&info.url) {
+                token.expires = token
+                    .expires
+                    .saturating_add(chrono::Local::now().timestamp());
+                self.session.write().unwrap().set_web_token(&token);
+            }
             Ok(info)
         }
     }
 
     /// 将url转换为token
+    /// expires为url中Expires的原始值, 旧接口中为有效秒数 (login_qr_info保存到会话时会换算为时间戳)
     pub fn login_qr_info_parse_token(&self, url: String) -> Result<WebToken> {
-        let regex = regex::Regex::new("^.+crossDomain\\?DedeUserID=(\\d+)&DedeUserID__ckMd5=([a-z0-9]+)&Expires=(\\d+)&SESSDATA=([^&]+)&bili_jct=([^&]+)&.+$")?;
-        let match_url = regex
-            .captures(url.as_str())
-            .ok_or(Error::msg("not match"))?;
-        let uid = match_url.get(1).ok_or(Error::msg("not match 1"))?.as_str();
-        let md5 = match_url.get(2).ok_or(Error::msg("not match 2"))?.as_str();
-        let exp = match_url.get(3).ok_or(Error::msg("not match 3"))?.as_str();
-        let sess = match_url.get(4).ok_or(Error::msg("not match 4"))?.as_str();
-        let jct = match_url.get(5).ok_or(Error::msg("not match 5"))?.as_str();
-        Ok(WebToken {
-            dedeuserid: FromStr::from_str(uid)?,
-            dedeuserid_ckmd5: md5.to_string(),
-            sessdata: sess.to_string(),
-            bili_jct: jct.to_string(),
-            expires: FromStr::from_str(exp)?,
-        })
+        parse_cross_domain_token(&url)
     }
 
     /// 登录 (注入token)
     pub fn login_set_sess_data(&mut self, sess_data: String) {
-        self.sess_data = Some(sess_data);
+        self.session
+            .write()
+            .unwrap()
+            .set(COOKIE_SESSDATA, sess_data);
+    }
+
+    /// 登录 (注入完整的token, 包含bili_jct等Cookie)
+    pub fn login_set_web_token(&mut self, web_token: &WebToken) {
+        self.session.write().unwrap().set_web_token(web_token);
+    }
+
+    /// 注入WEB登录时返回的refresh_token, 用于刷新Cookie
+    pub fn login_set_refresh_token(&mut self, refresh_token: String) {
+        self.session
+            .write()
+            .unwrap()
+            .set_refresh_token(refresh_token);
+    }
+
+    /// 登录 (注入TV登录的令牌)
+    pub fn login_set_tv_token(&mut self, tv_token: TvToken) {
+        self.session.write().unwrap().set_tv_token(tv_token);
+    }
+
+    /// 登录 (注入TV扫码登录的结果, 包含令牌以及返回的WEB Cookie)
+    pub fn login_set_tv_qr_info(&mut self, info: &LoginTvQrInfo) {
+        let mut session = self.session.write().unwrap();
+        session.set_tv_token(TvToken::from(info));
+        if let Some(cookie_info) = &info.cookie_info {
+            session.set_app_cookie_info(cookie_info);
+        }
+    }
+
+    /// 获取设备标识buvid3/buvid4并保存到会话, 部分接口缺少设备标识时会被风控
+    pub async fn fetch_buvid(&self) -> Result<FingerSpi> {
+        let spi: FingerSpi = self
+            .request_api(reqwest::Method::GET, "/x/frontend/finger/spi", None, None)
+            .await?;
+        let mut session = self.session.write().unwrap();
+        session.set(COOKIE_BUVID3, spi.b_3.as_str());
+        session.set(COOKIE_BUVID4, spi.b_4.as_str());
+        Ok(spi)
     }
 
     /// 个人信息, 登录后才能使用
     pub async fn my_info(&self) -> Result<MyInfo> {
-        Ok(self
-            .request_api(reqwest::Method::GET, "/x/space/myinfo", None, None)
-            .await?)
+        self.request_api(reqwest::Method::GET, "/x/space/myinfo", None, None)
+            .await
     }
 
-    /// 获取BV信息
+    /// 获取BV信息, 也可以使用AV号 (av170001)
     pub async fn bv_info(&self, bvid: String) -> Result<BvInfo> {
-        Ok(self
-            .request_api(
-                reqwest::Method::GET,
-                "/x/web-interface/view",
-                Option::Some(serde_json::json!({ "bvid": bvid })),
-                None,
-            )
-            .await?)
+        self.video_info(&bvid.parse()?).await
     }
 
     /// 获取BV信息(用AV号)
     pub async fn av_info(&self, avid: i64) -> Result<BvInfo> {
-        self.bv_info(av_to_bv(avid)).await
+        self.video_info(&VideoId::from_aid(avid)?).await
     }
 
-    /// 获取下载地址
+    /// 获取视频信息
+    pub async fn video_info(&self, id: &VideoId) -> Result<BvInfo> {
+        self.request_api(
+            reqwest::Method::GET,
+            "/x/web-interface/view",
+            Option::Some(serde_json::json!({ "bvid": id.bvid() })),
+            None,
+        )
+        .await
+    }
+
+    /// 获取下载地址, 需要更多参数时使用play_url
     pub async fn bv_download_url(
         &self,
         bvid: String,
         cid: i64,
-        fnval: i64,
+        fnval: impl Into<Fnval>,
         video_quality: VideoQuality,
     ) -> Result<VideoUrl> {
-        Ok(self
-            .request_api(
-                reqwest::Method::GET,
-                "/x/player/playurl",
-                Option::Some(serde_json::json!({
-                    "bvid": bvid,
-                    "cid":cid,
-                    "qn":video_quality.code,
-                    "fnval":fnval,
-                    "fnver":0,
-                    "fourk":1,
-                })),
-                None,
-            )
-            .await?)
+        self.request_play_url(
+            &PlayUrlRequest::new(bvid, cid)
+                .quality(video_quality)
+                .fnval(fnval),
+        )
+        .await
     }
 
     /// TV登录申请二维码
     pub async fn tv_login_qr(&self) -> Result<TvLoginQrData> {
-        let json = self
-            .request_passport(
-                reqwest::Method::POST,
-                "/x/passport-tv-login/qrcode/auth_code",
-                None,
-                Option::Some(sign_form(
-                    serde_json::json!({
-                        "appkey": APP_KEY_TV,
-                        "local_id": LOCAL_ID_TV,
-                        "ts": format!("{}", chrono::Local::now().second()),
-                    }),
-                    APP_SEC_TV,
-                )?),
-            )
-            .await?;
-        let code = &json["code"];
-        if !code.is_i64() {
-            return Err(Error::msg("err code format"));
-        }
-        if code.as_i64().unwrap() != 0 {
-            // todo
-            return Err(Error::msg("error"));
-        }
-        let data = json["data"].clone();
-        Ok(serde_json::from_value(data)?)
+        self.request_passport_data(
+            reqwest::Method::POST,
+            "/x/passport-tv-login/qrcode/auth_code",
+            None,
+            Option::Some(sign_form(
+                serde_json::json!({
+                    "appkey": APP_KEY_TV,
+                    "local_id": LOCAL_ID_TV,
+                    "ts": chrono::Local::now().timestamp().to_string(),
+                }),
+                APP_SEC_TV,
+            )?),
+        )
+        .await
     }
 
     /// 获取TV二维码登录信息
@@ -290,7 +582,7 @@ impl Client {
                         "appkey": APP_KEY_TV,
                         "auth_code": auth_code,
                         "local_id": LOCAL_ID_TV,
-                        "ts": format!("{}", chrono::Local::now().second()),
+                        "ts": chrono::Local::now().timestamp().to_string(),
                     }),
                     APP_SEC_TV,
                 )?),
@@ -299,36 +591,105 @@ impl Client {
         let value = json["data"].clone();
         if value.is_null() {
             Ok(LoginTvQrInfo {
-                error_data: json["code"].as_i64().ok_or(Error::msg("error format"))?,
-                mid: 0,
-                access_token: "".to_string(),
-                refresh_token: "".to_string(),
-                expires_in: 0,
+                error_data: json["code"]
+                    .as_i64()
+                    .ok_or_else(|| Error::Format("code应为数字类型".to_string()))?,
+                ..Default::default()
             })
         } else {
-            Ok(serde_json::from_value(value)?)
+            from_value(value)
         }
     }
 
     /// 获取视频信息
-    /// id: 例如 ep1234 ss1234
+    /// id: 例如 ep1234 ss1234 md1234
     pub async fn videos_info(&self, id: String) -> Result<web::SsState> {
-        self.videos_info_by_url(format!("https://www.bilibili.com/bangumi/play/{}", id))
+        self.videos_info_by_url(format!("{}/bangumi/play/{}", self.www_host, id))
             .await
     }
 
     /// 获取视频的信息
-    /// 例如 ep1234 ss1234 的 url
+    /// 例如 ep1234 ss1234 md1234 的 url
+    /// 优先使用接口 (/pgc/view/web/season), 失败时解析网页
     pub async fn videos_info_by_url(&self, url: String) -> Result<web::SsState> {
+        let regex = regex::Regex::new("/(ep|ss|md)(\\d+)").unwrap();
+        let (kind, id) = match regex.captures(&url) {
+            Some(captures) => (
+                captures[1].to_string(),
+                captures[2]
+                    .parse::<i64>()
+                    .map_err(|err| Error::InvalidArgument(err.to_string()))?,
+            ),
+            None => return self.videos_info_by_html(url).await,
+        };
+        let season = match kind.as_str() {
+            "ep" => self.pgc_season_by_ep(id).await,
+            "ss" => self.pgc_season(id).await,
+            _ => match self.pgc_media(id).await {
+                Ok(media) => self.pgc_season(media.season_id).await,
+                Err(err) => Err(err),
+            },
+        };
+        match season {
+            Ok(season) => Ok(season.into()),
+            Err(err) => {
+                debug!(target = "network", "Season api failed : {}", err);
+                self.videos_info_by_html(url).await.map_err(|html_err| {
+                    debug!(target = "network", "Season html failed : {}", html_err);
+                    err
+                })
+            }
+        }
+    }
+
+    /// 番剧/电影的剧集信息 (使用season_id)
+    pub async fn pgc_season(&self, season_id: i64) -> Result<PgcSeason> {
+        self.request_api(
+            reqwest::Method::GET,
+            "/pgc/view/web/season",
+            Some(serde_json::json!({ "season_id": season_id })),
+            None,
+        )
+        .await
+    }
+
+    /// 番剧/电影的剧集信息 (使用ep_id)
+    pub async fn pgc_season_by_ep(&self, ep_id: i64) -> Result<PgcSeason> {
+        self.request_api(
+            reqwest::Method::GET,
+            "/pgc/view/web/season",
+            Some(serde_json::json!({ "ep_id": ep_id })),
+            None,
+        )
+        .await
+    }
+
+    /// 番剧/电影的条目信息 (使用media_id)
+    pub async fn pgc_media(&self, media_id: i64) -> Result<PgcMedia> {
+        let review: PgcReviewUser = self
+            .request_api(
+                reqwest::Method::GET,
+                "/pgc/review/user",
+                Some(serde_json::json!({ "media_id": media_id })),
+                None,
+            )
+            .await?;
+        Ok(review.media)
+    }
+
+    /// 解析网页中的视频信息 (__NEXT_DATA__), 网页结构变化时会失败
+    pub async fn videos_info_by_html(&self, url: String) -> Result<web::SsState> {
         let rsp = self.request_web(url).await?;
         let start = r#"<script id="__NEXT_DATA__" type="application/json">"#;
         let stop = r#"</script>"#;
         let rsp: &str = match rsp.find(start) {
-            None => return Err(Error::msg("not found videos info data (1)")),
+            None => return Err(Error::Format("not found videos info data (1)".to_string())),
             Some(index) => {
                 let rsp = &rsp[(index + start.len())..];
                 match rsp.find(stop) {
-                    None => return Err(Error::msg("not found videos info data (2)")),
+                    None => {
+                        return Err(Error::Format("not found videos info data (2)".to_string()))
+                    }
                     Some(index) => &rsp[..index],
                 }
             }
@@ -336,17 +697,21 @@ impl Client {
         let json: serde_json::Value = serde_json::from_str(rsp)?;
         let season_json = json
             .get("props")
-            .ok_or(anyhow!("not found videos info data (props)"))?
+            .ok_or_else(|| Error::Format("not found videos info data (props)".to_string()))?
             .get("pageProps")
-            .ok_or(anyhow!("not found videos info data (pageProps)"))?
+            .ok_or_else(|| Error::Format("not found videos info data (pageProps)".to_string()))?
             .get("dehydratedState")
-            .ok_or(anyhow!("not found videos info data (dehydratedState)"))?
+            .ok_or_else(|| {
+                Error::Format("not found videos info data (dehydratedState)".to_string())
+            })?
             .get("queries")
-            .ok_or(anyhow!("not found videos info data (queries)"))?
+            .ok_or_else(|| Error::Format("not found videos info data (queries)".to_string()))?
             .as_array()
-            .ok_or(anyhow!("videos info data error (queries is non-array)"))?
+            .ok_or_else(|| {
+                Error::Format("videos info data error (queries is non-array)".to_string())
+            })?
             .iter()
-            .filter(|x| {
+            .find(|x| {
                 if let Some(query_key) = x.get("queryKey") {
                     if let Some(query_key) = query_key.as_array() {
                         for x in query_key {
@@ -358,33 +723,33 @@ impl Client {
                         }
                     }
                 }
-                return false;
+                false
             })
-            .nth(0)
-            .ok_or(anyhow!("not found videos info data (pgc/view/web/season)"))?;
+            .ok_or_else(|| {
+                Error::Format("not found videos info data (pgc/view/web/season)".to_string())
+            })?;
         let season_json = season_json
             .get("state")
-            .ok_or(anyhow!("not found videos info data (state)"))?
+            .ok_or_else(|| Error::Format("not found videos info data (state)".to_string()))?
             .get("data")
-            .ok_or(anyhow!("not found videos info data (data)"))?;
-        Ok(from_value(season_json.clone())?)
+            .ok_or_else(|| Error::Format("not found videos info data (data)".to_string()))?;
+        from_value(season_json.clone())
     }
 
     /// 获取当前登录用户信息
     pub async fn user_info(&self, mid: i64) -> Result<UserInfo> {
-        Ok(self
-            .request_api(
-                reqwest::Method::GET,
-                "/x/space/acc/info",
-                Some(serde_json::json!({
-                    "mid": mid,
-                    "token":"",
-                    "platform":"web",
-                    "jsonp":"jsonp",
-                })),
-                None,
-            )
-            .await?)
+        self.request_api_wbi(
+            reqwest::Method::GET,
+            "/x/space/wbi/acc/info",
+            Some(serde_json::json!({
+                "mid": mid,
+                "token":"",
+                "platform":"web",
+                "jsonp":"jsonp",
+            })),
+            None,
+        )
+        .await
     }
 
     // https://space.bilibili.com/{mid}/channel/series
@@ -396,18 +761,17 @@ impl Client {
         page_num: i64,
         page_size: i64,
     ) -> Result<SeasonsSeriesListData> {
-        Ok(self
-            .request_api(
-                reqwest::Method::GET,
-                "/x/polymer/space/seasons_series_list",
-                Some(serde_json::json!({
-                    "mid": mid,
-                    "page_num":page_num,
-                    "page_size":page_size,
-                })),
-                None,
-            )
-            .await?)
+        self.request_api(
+            reqwest::Method::GET,
+            "/x/polymer/space/seasons_series_list",
+            Some(serde_json::json!({
+                "mid": mid,
+                "page_num":page_num,
+                "page_size":page_size,
+            })),
+            None,
+        )
+        .await
     }
 
     // https://space.bilibili.com/{mid}/channel/series
@@ -436,34 +800,59 @@ impl Client {
         page_num: i64,
         page_size: i64,
     ) -> Result<CollectionDetailPage> {
-        Ok(self
-            .request_api(
-                reqwest::Method::GET,
-                "/x/polymer/space/seasons_archives_list",
-                Some(serde_json::json!({
-                    "mid": mid,
-                    "season_id":sid,
-                    "sort_reverse":sort_reverse,
-                    "page_num":page_num,
-                    "page_size":page_size,
-                })),
-                None,
-            )
-            .await?)
+        self.request_api(
+            reqwest::Method::GET,
+            "/x/polymer/space/seasons_archives_list",
+            Some(serde_json::json!({
+                "mid": mid,
+                "season_id":sid,
+                "sort_reverse":sort_reverse,
+                "page_num":page_num,
+                "page_size":page_size,
+            })),
+            None,
+        )
+        .await
     }
 
     /// 获取系列的信息
     pub async fn series_info(&self, series_id: i64) -> Result<SeriesVideoInfoData> {
-        Ok(self
-            .request_api(
-                reqwest::Method::GET,
-                "/x/series/series",
-                Some(serde_json::json!({
-                    "series_id": series_id,
-                })),
-                None,
-            )
-            .await?)
+        self.request_api(
+            reqwest::Method::GET,
+            "/x/series/series",
+            Some(serde_json::json!({
+                "series_id": series_id,
+            })),
+            None,
+        )
+        .await
+    }
+
+    // https://space.bilibili.com/{mid}/channel/seriesdetail?sid={series_id}
+    // page_num 1 开始
+    // page_size 请使用30
+    pub async fn series_video_page(
+        &self,
+        mid: i64,
+        series_id: i64,
+        sort_reverse: bool,
+        page_num: i64,
+        page_size: i64,
+    ) -> Result<SeriesArchivesPage> {
+        self.request_api(
+            reqwest::Method::GET,
+            "/x/series/archives",
+            Some(serde_json::json!({
+                "mid": mid,
+                "series_id": series_id,
+                "only_normal": true,
+                "sort": if sort_reverse { "asc" } else { "desc" },
+                "pn": page_num,
+                "ps": page_size,
+            })),
+            None,
+        )
+        .await
     }
 
     // https://space.bilibili.com/{mid}/favlist?fid={fid}
@@ -477,28 +866,119 @@ impl Client {
         keyword: Option<String>,
         order: FavListOrder,
     ) -> Result<FavListPage> {
-        Ok(self
-            .request_api(
-                reqwest::Method::GET,
-                "/x/v3/fav/resource/list",
-                Some(serde_json::json!({
-                    "media_id": fid,
-                    "pn":page_num,
-                    "ps":page_size,
-                    "keyword": if let Some(keyword) = keyword {
-                        keyword
-                    } else {
-                        "".to_string()
-                    },
-                    "order": order, // mtime (最新收藏), view (最多播放)， pubtime (发布时间)，
-                    "type":0,
-                    "platform":"web",
-                })),
-                None,
-            )
-            .await?)
+        self.request_api(
+            reqwest::Method::GET,
+            "/x/v3/fav/resource/list",
+            Some(serde_json::json!({
+                "media_id": fid,
+                "pn":page_num,
+                "ps":page_size,
+                "keyword": if let Some(keyword) = keyword {
+                    keyword
+                } else {
+                    "".to_string()
+                },
+                "order": order, // mtime (最新收藏), view (最多播放)， pubtime (发布时间)，
+                "type":0,
+                "platform":"web",
+            })),
+            None,
+        )
+        .await
+    }
+
+    /// 解析链接或编号, b23.tv短链接会跟随跳转后再解析
+    pub async fn resolve_resource(&self, input: &str) -> Result<Resource> {
+        match parse_resource(input)? {
+            Resource::ShortLink { url } => self.resolve_short_link(&url).await,
+            resource => Ok(resource),
+        }
+    }
+
+    /// 跟随短链接的跳转, 并解析跳转后的地址
+    /// 地址由调用者传入, 不携带会话中的Cookie
+    pub async fn resolve_short_link(&self, url: &str) -> Result<Resource> {
+        debug!(target = "network", "Resolve : {}", url);
+        let rsp = self.agent.get(url).send().await?;
+        match parse_resource(rsp.url().as_str())? {
+            Resource::ShortLink { .. } => {
+                Err(Error::InvalidArgument(format!("短链接没有跳转: {}", url)))
+            }
+            resource => Ok(resource),
+        }
+    }
+
+    /// 解析链接或编号, 并调用对应的接口
+    pub async fn resource_info(&self, input: &str) -> Result<ResourceInfo> {
+        Ok(match self.resolve_resource(input).await? {
+            Resource::Video { bvid, .. } => {
+                ResourceInfo::Video(Box::new(self.bv_info(bvid).await?))
+            }
+            Resource::Episode { ep_id, .. } => {
+                ResourceInfo::Season(Box::new(self.videos_info(format!("ep{}", ep_id)).await?))
+            }
+            Resource::Season { season_id } => ResourceInfo::Season(Box::new(
+                self.videos_info(format!("ss{}", season_id)).await?,
+            )),
+            Resource::Media { media_id } => {
+                ResourceInfo::Season(Box::new(self.videos_info(format!("md{}", media_id)).await?))
+            }
+            Resource::FavList { fid, .. } => ResourceInfo::FavList(Box::new(
+                self.fav_list_page(fid, 1, 20, None, FavListOrder::Mtime)
+                    .await?,
+            )),
+            Resource::Collection { mid, sid } => ResourceInfo::Collection(Box::new(
+                self.collection_video_page(mid, sid, false, 1, 30).await?,
+            )),
+            Resource::Series { sid, .. } => {
+                ResourceInfo::Series(Box::new(self.series_info(sid).await?))
+            }
+            Resource::User { mid } => ResourceInfo::User(Box::new(self.user_info(mid).await?)),
+            Resource::ShortLink { url } => {
+                return Err(Error::InvalidArgument(format!("短链接没有跳转: {}", url)))
+            }
+        })
     }
 }
 
+/// 检查返回的json中的code, 非0时转换为错误
+fn check_code(json: &serde_json::Value) -> Result<()> {
+    let code = json
+        .get("code")
+        .ok_or_else(|| Error::Format("服务器的响应中不包含code".to_string()))?
+        .as_i64()
+        .ok_or_else(|| Error::Format("code应为数字类型".to_string()))?;
+    if code != 0 {
+        return Err(Error::from_code(
+            code,
+            json["message"].as_str().unwrap_or_default(),
+            json["ttl"].as_i64().unwrap_or_default(),
+        ));
+    }
+    Ok(())
+}
+
+/// 解析登录成功后crossDomain跳转url中的token, Expires原样保存
+pub(crate) fn parse_cross_domain_token(url: &str) -> Result<WebToken> {
+    let regex = regex::Regex::new("^.+crossDomain\\?DedeUserID=(\\d+)&DedeUserID__ckMd5=([a-z0-9]+)&Expires=(\\d+)&SESSDATA=([^&]+)&bili_jct=([^&]+)&.+$").unwrap();
+    let match_url = regex
+        .captures(url)
+        .ok_or_else(|| Error::InvalidArgument(format!("无法从url中解析token: {}", url)))?;
+    let uid = &match_url[1];
+    let md5 = &match_url[2];
+    let exp = &match_url[3];
+    let sess = &match_url[4];
+    let jct = &match_url[5];
+    Ok(WebToken {
+        dedeuserid: FromStr::from_str(uid)
+            .map_err(|_| Error::InvalidArgument(format!("DedeUserID格式错误: {}", uid)))?,
+        dedeuserid_ckmd5: md5.to_string(),
+        sessdata: sess.to_string(),
+        bili_jct: jct.to_string(),
+        expires: FromStr::from_str(exp)
+            .map_err(|_| Error::InvalidArgument(format!("Expires格式错误: {}", exp)))?,
+    })
+}
+
 #[cfg(test)]
 pub mod test;
@@ -0,0 +1,550 @@
+use std::future::Future;
+use std::time::Duration;
+
+use base64::Engine;
+use futures::Stream;
+use rsa::pkcs8::DecodePublicKey;
+
+use crate::{
+    check_code, sign_form, AppTokenRefresh, Captcha, Client, CookieInfo, CookieRefresh, Error,
+    Geetest, GeetestResult, LoginKey, LoginTvQrInfo, Result, Session, SmsSend, TvToken,
+    WebLoginResult, WebQrData, WebQrPoll, WebQrStatus, WebToken, APP_KEY_TV, APP_SEC_TV,
+    LOCAL_ID_TV,
+};
+
+/// 生成CorrespondPath使用的公钥
+const CORRESPOND_PUBLIC_KEY: &str = "-----BEGIN PUBLIC KEY-----
+MIGfMA0GCSqGSIb3DQEBAQUAA4GNADCBiQKBgQDLgd2OAkcGVtoE3ThUREbio0Eg
+Uc/prcajMKXvkCKFCWhJYJcLkcM2DKKcSeFpD/j6Boy538YXnR6VhcuUJOhH2x71
+nzPjfdTcqMz7djHum0qSZA0AyCBDABUqCrfNgCiJ00Ra7GmRj+YCK1NJEuewlb40
+JNrRuoEUXpabUzGB8QIDAQAB
+-----END PUBLIC KEY-----";
+
+/// 生成刷新Cookie时使用的CorrespondPath
+/// 使用RSA-OAEP(SHA256)加密 refresh_{timestamp} 后转为16进制
+pub fn correspond_path(timestamp: i64) -> Result<String> {
+    let key = rsa::RsaPublicKey::from_public_key_pem(CORRESPOND_PUBLIC_KEY)
+        .map_err(|err| Error::InvalidArgument(err.to_string()))?;
+    let encrypted = key
+        .encrypt(
+            &mut rand::thread_rng(),
+            rsa::Oaep::new::<sha2::Sha256>(),
+            format!("refresh_{}", timestamp).as_bytes(),
+        )
+        .map_err(|err| Error::InvalidArgument(err.to_string()))?;
+    Ok(encrypted.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// WEB二维码登录
+impl Client {
+    /// WEB二维码登录 - 申请二维码
+    pub async fn login_qr_generate(&self) -> Result<WebQrData> {
+        self.request_passport_data(
+            reqwest::Method::GET,
+            "/x/passport-login/web/qrcode/generate",
+            None,
+            None,
+        )
+        .await
+    }
+
+    /// WEB二维码登录 - 查询扫码状态
+    /// 登录成功时响应中的Cookie与refresh_token会保存到会话, 同时放入返回结果的token中
+    pub async fn login_qr_poll(&self, qrcode_key: String) -> Result<WebQrPoll> {
+        let mut poll: WebQrPoll = self
+            .request_passport_data(
+                reqwest::Method::GET,
+                "/x/passport-login/web/qrcode/poll",
+                Some(serde_json::json!({ "qrcode_key": qrcode_key })),
+                None,
+            )
+            .await?;
+        if poll.status() == WebQrStatus::Confirmed {
+            poll.token = self.store_web_login(&poll.url, &poll.refresh_token);
+        }
+        Ok(poll)
+    }
+
+    /// 保存WEB登录成功的结果, 返回会话中的token
+    fn store_web_login(&self, url: &str, refresh_token: &str) -> Option<WebToken> {
+        let mut session = self.session.write().unwrap();
+        // Cookie已经从响应中保存, url中同样携带了token (以及Cookie中没有的过期时间戳)
+        if let Ok(token) = crate::parse_cross_domain_token(url) {
+            session.set_web_token(&token);
+        }
+        if !refresh_token.is_empty() {
+            session.set_refresh_token(refresh_token);
+        }
+        session.web_token()
+    }
+}
+
+/// 极验验证码的求解器, 由调用者实现 (人工处理或对接打码平台)
+/// 也可以直接传入 `|geetest: Geetest| async move { ... }` 形式的闭包
+pub trait GeetestSolver {
+    /// 完成验证, 返回validate与seccode
+    fn solve(&self, geetest: Geetest) -> impl Future<Output = Result<GeetestResult>> + Send;
+}
+
+impl<F, Fut> GeetestSolver for F
+where
+    F: Fn(Geetest) -> Fut,
+    Fut: Future<Output = Result<GeetestResult>> + Send,
+{
+    fn solve(&self, geetest: Geetest) -> impl Future<Output = Result<GeetestResult>> + Send {
+        self(geetest)
+    }
+}
+
+/// 加密登录密码
+/// 使用RSA(PKCS1v15)加密 hash+password 后转为base64
+pub fn encrypt_password(key: &LoginKey, password: &str) -> Result<String> {
+    let public_key = rsa::RsaPublicKey::from_public_key_pem(key.key.trim())
+        .map_err(|err| Error::InvalidArgument(err.to_string()))?;
+    let encrypted = public_key
+        .encrypt(
+            &mut rand::thread_rng(),
+            rsa::Pkcs1v15Encrypt,
+            format!("{}{}", key.hash, password).as_bytes(),
+        )
+        .map_err(|err| Error::InvalidArgument(err.to_string()))?;
+    Ok(base64::engine::general_purpose::STANDARD.encode(encrypted))
+}
+
+/// WEB短信/密码登录
+impl Client {
+    /// 申请人机验证
+    pub async fn login_captcha(&self) -> Result<Captcha> {
+        self.request_passport_data(
+            reqwest::Method::GET,
+            "/x/passport-login/captcha",
+            Some(serde_json::json!({ "source": "main_web" })),
+            None,
+        )
+        .await
+    }
+
+    /// 申请人机验证并交给solver完成, 返回提交时需要携带的参数
+    async fn solve_captcha(&self, solver: &impl GeetestSolver) -> Result<serde_json::Value> {
+        let captcha = self.login_captcha().await?;
+        let result = solver.solve(captcha.geetest).await?;
+        Ok(serde_json::json!({
+            "token": captcha.token,
+            "challenge": result.challenge,
+            "validate": result.validate,
+            "seccode": result.seccode,
+        }))
+    }
+
+    /// 短信登录 - 发送验证码, cid为国际冠字码 (中国大陆为86)
+    /// 返回的captcha_key在登录时使用
+    pub async fn login_sms_send(
+        &self,
+        cid: i64,
+        tel: String,
+        solver: &impl GeetestSolver,
+    ) -> Result<SmsSend> {
+        let mut form = self.solve_captcha(solver).await?;
+        form["cid"] = cid.into();
+        form["tel"] = tel.into();
+        form["source"] = "main_web".into();
+        self.request_passport_data(
+            reqwest::Method::POST,
+            "/x/passport-login/web/sms/send",
+            None,
+            Some(form),
+        )
+        .await
+    }
+
+    /// 短信登录 - 使用验证码登录
+    /// 登录成功时响应中的Cookie与refresh_token会保存到会话, 同时放入返回结果的token中
+    pub async fn login_sms(
+        &self,
+        cid: i64,
+        tel: String,
+        code: String,
+        captcha_key: String,
+    ) -> Result<WebLoginResult> {
+        let result = self
+            .request_passport_data(
+                reqwest::Method::POST,
+                "/x/passport-login/web/login/sms",
+                None,
+                Some(serde_json::json!({
+                    "cid": cid,
+                    "tel": tel,
+                    "code": code,
+                    "captcha_key": captcha_key,
+                    "source": "main_web",
+                    "go_url": "https://www.bilibili.com",
+                })),
+            )
+            .await?;
+        Ok(self.finish_web_login(result))
+    }
+
+    /// 密码登录 - 获取加密密码使用的公钥与盐
+    pub async fn login_key(&self) -> Result<LoginKey> {
+        self.request_passport_data(
+            reqwest::Method::GET,
+            "/x/passport-login/web/key",
+            None,
+            None,
+        )
+        .await
+    }
+
+    /// 密码登录
+    /// 登录成功时响应中的Cookie与refresh_token会保存到会话, 同时放入返回结果的token中
+    /// status不为0时需要打开url进行进一步验证
+    pub async fn login_password(
+        &self,
+        username: String,
+        password: String,
+        solver: &impl GeetestSolver,
+    ) -> Result<WebLoginResult> {
+        let mut form = self.solve_captcha(solver).await?;
+        let key = self.login_key().await?;
+        form["username"] = username.into();
+        form["password"] = encrypt_password(&key, &password)?.into();
+        form["keep"] = 0.into();
+        form["source"] = "main_web".into();
+        form["go_url"] = "https://www.bilibili.com".into();
+        let result = self
+            .request_passport_data(
+                reqwest::Method::POST,
+                "/x/passport-login/web/login",
+                None,
+                Some(form),
+            )
+            .await?;
+        Ok(self.finish_web_login(result))
+    }
+
+    fn finish_web_login(&self, mut result: WebLoginResult) -> WebLoginResult {
+        if result.status == 0 {
+            result.token = self.store_web_login(&result.url, &result.refresh_token);
+        }
+        result
+    }
+}
+
+/// 退出登录
+impl Client {
+    /// 退出WEB登录, 服务器端的SESSDATA会失效, 成功后清除会话中的登录Cookie与refresh_token
+    pub async fn logout(&self) -> Result<()> {
+        let csrf = self
+            .csrf()
+            .ok_or_else(|| Error::InvalidArgument("缺少bili_jct".to_string()))?;
+        let json = self
+            .request_passport(
+                reqwest::Method::POST,
+                "/login/exit/v2",
+                None,
+                Some(serde_json::json!({ "biliCSRF": csrf })),
+            )
+            .await?;
+        check_code(&json)?;
+        self.session.write().unwrap().clear_web_login();
+        Ok(())
+    }
+}
+
+/// 扫码登录的状态
+#[derive(Debug, Clone, PartialEq)]
+pub enum QrLoginState<T> {
+    /// 等待扫码
+    Waiting,
+    /// 已扫码, 等待确认
+    Scanned,
+    /// 二维码已失效, 需要重新申请
+    Expired,
+    /// 登录成功
+    Confirmed(T),
+}
+
+/// 扫码登录的轮询参数
+#[derive(Debug, Clone)]
+pub struct QrLoginPoller {
+    interval: Duration,
+    timeout: Option<Duration>,
+}
+
+impl Default for QrLoginPoller {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl QrLoginPoller {
+    /// 默认每2秒查询一次, 3分钟后超时 (二维码的有效期)
+    pub fn new() -> Self {
+        Self {
+            interval: Duration::from_secs(2),
+            timeout: Some(Duration::from_secs(180)),
+        }
+    }
+
+    /// 查询间隔
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// 总的超时时间, None为不超时
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// 轮询直到登录成功/二维码失效/出错/超时
+    /// 状态变化时才会返回, 超时返回Error::Timeout, 丢弃Stream即可取消轮询
+    pub fn stream<T, F, Fut>(&self, poll: F) -> impl Stream<Item = Result<QrLoginState<T>>>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<QrLoginState<T>>>,
+    {
+        let interval = self.interval;
+        let deadline = self
+            .timeout
+            .map(|timeout| tokio::time::Instant::now() + timeout);
+        futures::stream::unfold(
+            (poll, None, false, true),
+            move |(mut poll, mut last, finished, first)| async move {
+                if finished {
+                    return None;
+                }
+                let mut first = first;
+                loop {
+                    // 等待间隔和请求都计入超时
+                    let step = async {
+                        if !first {
+                            tokio::time::sleep(interval).await;
+                        }
+                        poll().await
+                    };
+                    let state = match deadline {
+                        Some(deadline) => tokio::time::timeout_at(deadline, step)
+                            .await
+                            .unwrap_or(Err(Error::Timeout)),
+                        None => step.await,
+                    };
+                    first = false;
+                    match state {
+                        Err(err) => return Some((Err(err), (poll, last, true, false))),
+                        Ok(state) => {
+                            let finished =
+                                matches!(state, QrLoginState::Confirmed(_) | QrLoginState::Expired);
+                            let discriminant = std::mem::discriminant(&state);
+                            if finished || last != Some(discriminant) {
+                                last = Some(discriminant);
+                                return Some((Ok(state), (poll, last, finished, false)));
+                            }
+                        }
+                    }
+                }
+            },
+        )
+    }
+}
+
+/// 扫码登录轮询
+impl Client {
+    /// WEB二维码登录 - 轮询扫码状态, 登录成功后会话会自动更新
+    pub fn login_qr_stream(
+        &self,
+        qrcode_key: String,
+        poller: &QrLoginPoller,
+    ) -> impl Stream<Item = Result<QrLoginState<WebToken>>> {
+        let client = self.clone();
+        poller.stream(move || {
+            let client = client.clone();
+            let qrcode_key = qrcode_key.clone();
+            async move {
+                let poll = client.login_qr_poll(qrcode_key).await?;
+                match poll.status() {
+                    WebQrStatus::Confirmed => {
+                        Ok(QrLoginState::Confirmed(poll.token.ok_or_else(|| {
+                            Error::Format("登录成功但没有返回token".to_string())
+                        })?))
+                    }
+                    WebQrStatus::Expired => Ok(QrLoginState::Expired),
+                    WebQrStatus::Scanned => Ok(QrLoginState::Scanned),
+                    WebQrStatus::NotScanned => Ok(QrLoginState::Waiting),
+                    WebQrStatus::Unknown(code) => Err(Error::from_code(code, poll.message, 0)),
+                }
+            }
+        })
+    }
+
+    /// TV二维码登录 - 轮询扫码状态, 登录成功后令牌与WEB Cookie会自动保存到会话
+    pub fn tv_login_qr_stream(
+        &self,
+        auth_code: String,
+        poller: &QrLoginPoller,
+    ) -> impl Stream<Item = Result<QrLoginState<LoginTvQrInfo>>> {
+        let client = self.clone();
+        poller.stream(move || {
+            let client = client.clone();
+            let auth_code = auth_code.clone();
+            async move {
+                let info = client.tv_login_qr_info(auth_code).await?;
+                match info.error_data {
+                    0 => {
+                        let mut session = client.session.write().unwrap();
+                        session.set_tv_token(TvToken::from(&info));
+                        if let Some(cookie_info) = &info.cookie_info {
+                            session.set_app_cookie_info(cookie_info);
+                        }
+                        Ok(QrLoginState::Confirmed(info))
+                    }
+                    86038 => Ok(QrLoginState::Expired),
+                    86039 => Ok(QrLoginState::Waiting),
+                    86090 => Ok(QrLoginState::Scanned),
+                    code => Err(Error::from_code(code, "TV二维码登录失败", 0)),
+                }
+            }
+        })
+    }
+}
+
+/// WEB Cookie刷新
+impl Client {
+    /// 检查Cookie是否需要刷新
+    pub async fn cookie_info(&self) -> Result<CookieInfo> {
+        self.request_passport_data(
+            reqwest::Method::GET,
+            "/x/passport-login/web/cookie/info",
+            Some(serde_json::json!({
+                "csrf": self.csrf().unwrap_or_default(),
+            })),
+            None,
+        )
+        .await
+    }
+
+    /// 获取刷新Cookie需要的refresh_csrf
+    pub async fn refresh_csrf(&self, timestamp: i64) -> Result<String> {
+        let html = self
+            .request_web(format!(
+                "{}/correspond/1/{}",
+                self.www_host,
+                correspond_path(timestamp)?
+            ))
+            .await?;
+        let regex = regex::Regex::new(r#"<div id="1-name">([^<]+)</div>"#).unwrap();
+        Ok(regex
+            .captures(&html)
+            .ok_or_else(|| Error::Format("未能获取到refresh_csrf".to_string()))?[1]
+            .trim()
+            .to_string())
+    }
+
+    /// 刷新Cookie, 需要登录时返回的refresh_token
+    /// 刷新成功后会话中的Cookie与refresh_token会被替换, 旧的refresh_token失效
+    pub async fn refresh_web_cookie(&self) -> Result<CookieRefresh> {
+        let info = self.cookie_info().await?;
+        self.rotate_web_cookie(info.timestamp).await
+    }
+
+    /// 检查Cookie是否需要刷新, 需要时进行刷新, 返回是否进行了刷新
+    pub async fn refresh_web_cookie_if_needed(&self) -> Result<bool> {
+        let info = self.cookie_info().await?;
+        if info.refresh {
+            self.rotate_web_cookie(info.timestamp).await?;
+        }
+        Ok(info.refresh)
+    }
+
+    async fn rotate_web_cookie(&self, timestamp: i64) -> Result<CookieRefresh> {
+        let old_refresh_token = self
+            .session()
+            .refresh_token()
+            .ok_or_else(|| Error::InvalidArgument("缺少refresh_token".to_string()))?
+            .to_string();
+        let refresh_csrf = self.refresh_csrf(timestamp).await?;
+        let refresh: CookieRefresh = self
+            .request_passport_data(
+                reqwest::Method::POST,
+                "/x/passport-login/web/cookie/refresh",
+                None,
+                Some(serde_json::json!({
+                    "csrf": self.csrf().unwrap_or_default(),
+                    "refresh_csrf": refresh_csrf,
+                    "source": "main_web",
+                    "refresh_token": old_refresh_token,
+                })),
+            )
+            .await?;
+        self.session
+            .write()
+            .unwrap()
+            .set_refresh_token(refresh.refresh_token.as_str());
+        // 使用新的bili_jct确认刷新, 使旧的refresh_token失效
+        let json = self
+            .request_passport(
+                reqwest::Method::POST,
+                "/x/passport-login/web/confirm/refresh",
+                None,
+                Some(serde_json::json!({
+                    "csrf": self.csrf().unwrap_or_default(),
+                    "refresh_token": old_refresh_token,
+                })),
+            )
+            .await?;
+        check_code(&json)?;
+        Ok(refresh)
+    }
+}
+
+/// TV/APP令牌
+impl Client {
+    /// 刷新TV登录的令牌, 刷新后会话中的令牌与WEB Cookie会被替换
+    pub async fn refresh_tv_token(&self) -> Result<AppTokenRefresh> {
+        let tv_token = self
+            .session()
+            .tv_token()
+            .cloned()
+            .ok_or_else(|| Error::InvalidArgument("缺少TV登录的令牌".to_string()))?;
+        let refresh: AppTokenRefresh = self
+            .request_passport_data(
+                reqwest::Method::POST,
+                "/x/passport-login/oauth2/refresh_token",
+                None,
+                Some(sign_form(
+                    serde_json::json!({
+                        "access_key": tv_token.access_token,
+                        "appkey": APP_KEY_TV,
+                        "local_id": LOCAL_ID_TV,
+                        "refresh_token": tv_token.refresh_token,
+                        "ts": chrono::Local::now().timestamp().to_string(),
+                    }),
+                    APP_SEC_TV,
+                )?),
+            )
+            .await?;
+        let mut session = self.session.write().unwrap();
+        session.set_tv_token(TvToken::from(&refresh.token_info));
+        if let Some(cookie_info) = &refresh.cookie_info {
+            session.set_app_cookie_info(cookie_info);
+        }
+        Ok(refresh)
+    }
+
+    /// 使用TV登录的令牌换取WEB Cookie
+    /// 通过刷新令牌接口返回的cookie_info实现, 注意TV令牌也会被一同刷新 (同refresh_tv_token)
+    /// 响应中没有cookie_info时返回错误, 不会返回会话中原有的WEB Cookie
+    pub async fn exchange_web_cookie(&self) -> Result<WebToken> {
+        let refresh = self.refresh_tv_token().await?;
+        let cookie_info = refresh
+            .cookie_info
+            .as_ref()
+            .ok_or_else(|| Error::Format("服务器没有返回WEB Cookie".to_string()))?;
+        let mut session = Session::new();
+        session.set_app_cookie_info(cookie_info);
+        session
+            .web_token()
+            .ok_or_else(|| Error::Format("cookie_info中不包含SESSDATA".to_string()))
+    }
+}
@@ -0,0 +1,206 @@
+//! 将DASH播放地址转换为标准的MPEG-DASH MPD, 供dash.js/mpv/ExoPlayer等播放器直接使用
+
+use std::fmt::Write;
+
+use crate::{Audio, Dash, Video, VideoUrl};
+
+/// 转换为MPD
+impl Dash {
+    /// 生成MPD (XML), 时长使用dash.duration (秒)
+    /// 视频按编码分为多个AdaptationSet, 备用地址作为额外的BaseURL
+    pub fn to_mpd(&self) -> String {
+        self.to_mpd_with_duration(self.duration as f64)
+    }
+
+    /// 生成MPD (XML), 指定时长 (秒)
+    pub fn to_mpd_with_duration(&self, duration: f64) -> String {
+        let mut mpd = String::new();
+        mpd.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let min_buffer_time = if self.min_buffer_time > 0.0 {
+            self.min_buffer_time
+        } else {
+            self.min_buffer_time2
+        };
+        let _ = writeln!(
+            mpd,
+            "<MPD xmlns=\"urn:mpeg:dash:schema:mpd:2011\" profiles=\"urn:mpeg:dash:profile:isoff-on-demand:2011\" type=\"static\" mediaPresentationDuration=\"{}\" minBufferTime=\"{}\">",
+            iso_duration(duration),
+            iso_duration(min_buffer_time),
+        );
+        mpd.push_str("  <Period id=\"0\" start=\"PT0S\">\n");
+
+        let mut codecids: Vec<i64> = vec![];
+        for video in &self.video {
+            if !codecids.contains(&video.codecid) {
+                codecids.push(video.codecid);
+            }
+        }
+        let mut set_id = 0;
+        for codecid in codecids {
+            let videos: Vec<&Video> = self
+                .video
+                .iter()
+                .filter(|video| video.codecid == codecid)
+                .collect();
+            let mime_type =
+                first_non_empty(&videos[0].mime_type, &videos[0].mime_type2, "video/mp4");
+            let _ = writeln!(
+                mpd,
+                "    <AdaptationSet id=\"{}\" contentType=\"video\" mimeType=\"{}\" segmentAlignment=\"true\" subsegmentAlignment=\"true\" subsegmentStartsWithSAP=\"1\">",
+                set_id,
+                escape(mime_type),
+            );
+            for video in videos {
+                let frame_rate = first_non_empty(&video.frame_rate, &video.frame_rate2, "");
+                let _ = write!(
+                    mpd,
+                    "      <Representation id=\"{}-{}\" bandwidth=\"{}\" codecs=\"{}\" width=\"{}\" height=\"{}\"",
+                    video.id,
+                    video.codecid,
+                    video.bandwidth,
+                    escape(&video.codecs),
+                    video.width,
+                    video.height,
+                );
+                if !frame_rate.is_empty() {
+                    let _ = write!(mpd, " frameRate=\"{}\"", escape(frame_rate));
+                }
+                if !video.sar.is_empty() {
+                    let _ = write!(mpd, " sar=\"{}\"", escape(&video.sar));
+                }
+                mpd.push_str(">\n");
+                write_urls_and_segment(
+                    &mut mpd,
+                    &video.urls(),
+                    &video.segment_base.initialization,
+                    &video.segment_base.index_range,
+                );
+                mpd.push_str("      </Representation>\n");
+            }
+            mpd.push_str("    </AdaptationSet>\n");
+            set_id += 1;
+        }
+
+        // 普通音频, 杜比音频, Hi-Res无损音频的编码不同, 不能在同一个AdaptationSet中切换
+        let mut audio_sets: Vec<&[Audio]> = vec![&self.audio];
+        if let Some(dolby) = &self.dolby {
+            audio_sets.push(&dolby.audio);
+        }
+        if let Some(Some(audio)) = self.flac.as_ref().map(|flac| &flac.audio) {
+            audio_sets.push(std::slice::from_ref(audio));
+        }
+        for audios in audio_sets.into_iter().filter(|audios| !audios.is_empty()) {
+            let first = &audios[0];
+            let mime_type = first_non_empty(&first.mime_type, &first.mime_type2, "audio/mp4");
+            let _ = writeln!(
+                mpd,
+                "    <AdaptationSet id=\"{}\" contentType=\"audio\" mimeType=\"{}\" codecs=\"{}\" segmentAlignment=\"true\" subsegmentAlignment=\"true\" subsegmentStartsWithSAP=\"1\">",
+                set_id,
+                escape(mime_type),
+                escape(&first.codecs),
+            );
+            for audio in audios {
+                write_audio(&mut mpd, audio);
+            }
+            mpd.push_str("    </AdaptationSet>\n");
+            set_id += 1;
+        }
+
+        mpd.push_str("  </Period>\n");
+        mpd.push_str("</MPD>\n");
+        mpd
+    }
+}
+
+/// 转换为MPD
+impl VideoUrl {
+    /// 生成MPD (XML), 时长使用更精确的timelength (毫秒)
+    pub fn to_mpd(&self) -> String {
+        if self.timelength > 0 {
+            self.dash
+                .to_mpd_with_duration(self.timelength as f64 / 1000.0)
+        } else {
+            self.dash.to_mpd()
+        }
+    }
+}
+
+fn write_audio(mpd: &mut String, audio: &Audio) {
+    let _ = writeln!(
+        mpd,
+        "      <Representation id=\"{}\" bandwidth=\"{}\" codecs=\"{}\">",
+        audio.id,
+        audio.bandwidth,
+        escape(&audio.codecs),
+    );
+    write_urls_and_segment(
+        mpd,
+        &audio.urls(),
+        &audio.segment_base.initialization,
+        &audio.segment_base.index_range,
+    );
+    mpd.push_str("      </Representation>\n");
+}
+
+fn write_urls_and_segment(
+    mpd: &mut String,
+    urls: &[String],
+    initialization: &str,
+    index_range: &str,
+) {
+    for url in urls {
+        let _ = writeln!(mpd, "        <BaseURL>{}</BaseURL>", escape(url));
+    }
+    if initialization.is_empty() && index_range.is_empty() {
+        return;
+    }
+    if index_range.is_empty() {
+        mpd.push_str("        <SegmentBase>\n");
+    } else {
+        let _ = writeln!(
+            mpd,
+            "        <SegmentBase indexRange=\"{}\">",
+            escape(index_range)
+        );
+    }
+    if !initialization.is_empty() {
+        let _ = writeln!(
+            mpd,
+            "          <Initialization range=\"{}\"/>",
+            escape(initialization)
+        );
+    }
+    mpd.push_str("        </SegmentBase>\n");
+}
+
+fn first_non_empty<'a>(a: &'a str, b: &'a str, default: &'a str) -> &'a str {
+    if !a.is_empty() {
+        a
+    } else if !b.is_empty() {
+        b
+    } else {
+        default
+    }
+}
+
+/// ISO 8601 时长, 如 PT123.456S
+fn iso_duration(seconds: f64) -> String {
+    let seconds = format!("{:.3}", seconds.max(0.0));
+    let seconds = seconds.trim_end_matches('0').trim_end_matches('.');
+    format!("PT{}S", seconds)
+}
+
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
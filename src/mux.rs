@@ -0,0 +1,632 @@
+//! 将DASH的视频与音频 (fMP4/m4s) 无损合并为MP4 (需要开启mux特性)
+//!
+//! 只重写索引 (moov/stbl), 媒体数据原样复制, 不会重新编码
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::{Error, Result, SegmentBase};
+
+/// sample_flags中的sample_is_non_sync_sample
+const SAMPLE_NON_SYNC: u32 = 0x0001_0000;
+
+/// 合并的输入轨道
+#[derive(Debug, Clone, PartialEq)]
+pub struct MuxTrack {
+    path: PathBuf,
+    segment_base: Option<SegmentBase>,
+}
+
+impl MuxTrack {
+    /// 构造方法, path为下载得到的m4s文件
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            segment_base: None,
+        }
+    }
+
+    /// 使用播放地址中的SegmentBase定位初始化段 (moov) 与索引 (sidx), 跳过逐个扫描文件头
+    pub fn segment_base(mut self, segment_base: SegmentBase) -> Self {
+        self.segment_base = Some(segment_base);
+        self
+    }
+}
+
+/// 合并视频与音频为MP4
+/// 为同步IO, 在异步环境中请使用spawn_blocking
+pub fn remux_dash(
+    video: impl AsRef<Path>,
+    audio: impl AsRef<Path>,
+    output: impl AsRef<Path>,
+) -> Result<()> {
+    remux(
+        &[MuxTrack::new(video.as_ref()), MuxTrack::new(audio.as_ref())],
+        output,
+    )
+}
+
+/// 合并多个单轨道的fMP4为MP4, 轨道按传入的顺序排列
+/// 为同步IO, 在异步环境中请使用spawn_blocking
+pub fn remux(tracks: &[MuxTrack], output: impl AsRef<Path>) -> Result<()> {
+    if tracks.is_empty() {
+        return Err(Error::InvalidArgument("没有需要合并的轨道".to_string()));
+    }
+    let mut inputs = tracks
+        .iter()
+        .map(Track::open)
+        .collect::<Result<Vec<Track>>>()?;
+
+    // 按时间交错排列各轨道的数据块, 便于边下边播
+    let mut order: Vec<(f64, usize, usize)> = vec![];
+    for (track_index, track) in inputs.iter().enumerate() {
+        let mut time = 0u64;
+        for (run_index, run) in track.runs.iter().enumerate() {
+            order.push((time as f64 / track.timescale as f64, track_index, run_index));
+            time += track.samples[run.first_sample..run.first_sample + run.samples]
+                .iter()
+                .map(|sample| sample.duration as u64)
+                .sum::<u64>();
+        }
+    }
+    order.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+    let payload: u64 = inputs
+        .iter()
+        .flat_map(|track| track.runs.iter())
+        .map(|run| run.len)
+        .sum();
+    let co64 = payload > u32::MAX as u64 - (64 << 20);
+
+    let ftyp = make_box(
+        b"ftyp",
+        &[&b"isom"[..], &512u32.to_be_bytes(), b"isomiso2avc1mp41"].concat(),
+    );
+    // moov的大小与偏移量的取值无关, 先计算大小再填入实际偏移量
+    let moov_len = build_moov(&inputs, co64)?.len() as u64;
+    let mut offset = ftyp.len() as u64 + moov_len + 16;
+    for (_, track_index, run_index) in &order {
+        let run = &mut inputs[*track_index].runs[*run_index];
+        run.output_offset = offset;
+        offset += run.len;
+    }
+    let moov = build_moov(&inputs, co64)?;
+
+    let mut writer = BufWriter::new(File::create(output.as_ref())?);
+    writer.write_all(&ftyp)?;
+    writer.write_all(&moov)?;
+    writer.write_all(&1u32.to_be_bytes())?;
+    writer.write_all(b"mdat")?;
+    writer.write_all(&(payload + 16).to_be_bytes())?;
+    for (_, track_index, run_index) in &order {
+        let track = &mut inputs[*track_index];
+        let run = &track.runs[*run_index];
+        track.reader.seek(SeekFrom::Start(run.input_offset))?;
+        let copied = std::io::copy(&mut (&mut track.reader).take(run.len), &mut writer)?;
+        if copied != run.len {
+            return Err(Error::Format("媒体数据不完整".to_string()));
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Sample {
+    size: u32,
+    duration: u32,
+    flags: u32,
+    cto: i32,
+}
+
+/// 一段连续的数据 (对应一个trun), 输出时作为一个chunk
+#[derive(Debug, Clone, Copy, Default)]
+struct Run {
+    input_offset: u64,
+    output_offset: u64,
+    len: u64,
+    first_sample: usize,
+    samples: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct TrackDefaults {
+    duration: u32,
+    size: u32,
+    flags: u32,
+}
+
+struct Track {
+    reader: BufReader<File>,
+    moov: Vec<u8>,
+    trak: Vec<u8>,
+    timescale: u32,
+    handler: [u8; 4],
+    samples: Vec<Sample>,
+    runs: Vec<Run>,
+}
+
+impl Track {
+    fn open(input: &MuxTrack) -> Result<Self> {
+        let file = File::open(&input.path)?;
+        let end = file.metadata()?.len();
+        let mut reader = BufReader::new(file);
+        let mut moov = None;
+        let mut offset = 0;
+        if let Some(segment_base) = &input.segment_base {
+            let (init_start, init_end) = segment_base
+                .initialization_range()
+                .ok_or_else(|| Error::InvalidArgument("initialization格式错误".to_string()))?;
+            let mut init = vec![0; (init_end + 1 - init_start) as usize];
+            reader.seek(SeekFrom::Start(init_start))?;
+            reader.read_exact(&mut init)?;
+            moov = children(&init)?
+                .into_iter()
+                .find(|(typ, _, _)| typ == b"moov")
+                .map(|(_, full, _)| full.to_vec());
+            offset = segment_base
+                .index_range_bounds()
+                .map(|(_, index_end)| index_end + 1)
+                .unwrap_or(init_end + 1);
+        }
+        let mut moofs = vec![];
+        while let Some((typ, size)) = read_box_header(&mut reader, offset, end)? {
+            if &typ == b"moov" || &typ == b"moof" {
+                let mut data = vec![0; size as usize];
+                reader.seek(SeekFrom::Start(offset))?;
+                reader.read_exact(&mut data)?;
+                if &typ == b"moov" {
+                    moov = Some(data);
+                } else {
+                    moofs.push((offset, data));
+                }
+            }
+            offset += size;
+        }
+        let moov = moov.ok_or_else(|| Error::Format("缺少moov".to_string()))?;
+        let moov_payload = payload(&moov)?;
+        let trak = find(moov_payload, b"trak")?.to_vec();
+        let mdia = payload(find(payload(&trak)?, b"mdia")?)?;
+        let tkhd = payload(find(payload(&trak)?, b"tkhd")?)?;
+        let track_id = read_u32(tkhd, full_offset(tkhd, 12, 20)?)?;
+        let mdhd = payload(find(mdia, b"mdhd")?)?;
+        let timescale = read_u32(mdhd, full_offset(mdhd, 12, 20)?)?;
+        let hdlr = payload(find(mdia, b"hdlr")?)?;
+        let handler: [u8; 4] = hdlr
+            .get(8..12)
+            .and_then(|h| h.try_into().ok())
+            .ok_or_else(|| Error::Format("hdlr格式错误".to_string()))?;
+        let mut defaults = TrackDefaults::default();
+        if let Ok(mvex) = find(moov_payload, b"mvex") {
+            for (typ, _, trex) in children(payload(mvex)?)? {
+                if &typ == b"trex" && read_u32(trex, 4)? == track_id {
+                    defaults = TrackDefaults {
+                        duration: read_u32(trex, 12)?,
+                        size: read_u32(trex, 16)?,
+                        flags: read_u32(trex, 20)?,
+                    };
+                }
+            }
+        }
+        let mut track = Track {
+            reader,
+            moov,
+            trak,
+            timescale,
+            handler,
+            samples: vec![],
+            runs: vec![],
+        };
+        for (moof_offset, moof) in &moofs {
+            track.parse_moof(*moof_offset, moof, defaults)?;
+        }
+        if track.samples.is_empty() {
+            return Err(Error::Format("没有找到媒体数据".to_string()));
+        }
+        Ok(track)
+    }
+
+    fn parse_moof(&mut self, moof_offset: u64, moof: &[u8], trex: TrackDefaults) -> Result<()> {
+        for (typ, _, traf) in children(payload(moof)?)? {
+            if &typ != b"traf" {
+                continue;
+            }
+            let tfhd = payload(find(traf, b"tfhd")?)?;
+            let tf_flags = read_u32(tfhd, 0)? & 0x00FF_FFFF;
+            let mut pos = 8;
+            let mut base = moof_offset;
+            let mut defaults = trex;
+            if tf_flags & 0x01 != 0 {
+                base = read_u64(tfhd, pos)?;
+                pos += 8;
+            }
+            if tf_flags & 0x02 != 0 {
+                pos += 4;
+            }
+            if tf_flags & 0x08 != 0 {
+                defaults.duration = read_u32(tfhd, pos)?;
+                pos += 4;
+            }
+            if tf_flags & 0x10 != 0 {
+                defaults.size = read_u32(tfhd, pos)?;
+                pos += 4;
+            }
+            if tf_flags & 0x20 != 0 {
+                defaults.flags = read_u32(tfhd, pos)?;
+            }
+            let mut data_offset = base;
+            for (typ, _, trun) in children(traf)? {
+                if &typ != b"trun" {
+                    continue;
+                }
+                let flags = read_u32(trun, 0)? & 0x00FF_FFFF;
+                let count = read_u32(trun, 4)? as usize;
+                let mut pos = 8;
+                if flags & 0x01 != 0 {
+                    data_offset = base.wrapping_add_signed(read_u32(trun, pos)? as i32 as i64);
+                    pos += 4;
+                }
+                let mut first_flags = None;
+                if flags & 0x04 != 0 {
+                    first_flags = Some(read_u32(trun, pos)?);
+                    pos += 4;
+                }
+                let first_sample = self.samples.len();
+                let mut len = 0u64;
+                for i in 0..count {
+                    let mut sample = Sample {
+                        size: defaults.size,
+                        duration: defaults.duration,
+                        flags: defaults.flags,
+                        cto: 0,
+                    };
+                    if flags & 0x100 != 0 {
+                        sample.duration = read_u32(trun, pos)?;
+                        pos += 4;
+                    }
+                    if flags & 0x200 != 0 {
+                        sample.size = read_u32(trun, pos)?;
+                        pos += 4;
+                    }
+                    if flags & 0x400 != 0 {
+                        sample.flags = read_u32(trun, pos)?;
+                        pos += 4;
+                    } else if i == 0 {
+                        sample.flags = first_flags.unwrap_or(sample.flags);
+                    }
+                    if flags & 0x800 != 0 {
+                        sample.cto = read_u32(trun, pos)? as i32;
+                        pos += 4;
+                    }
+                    len += sample.size as u64;
+                    self.samples.push(sample);
+                }
+                if count > 0 {
+                    self.runs.push(Run {
+                        input_offset: data_offset,
+                        output_offset: 0,
+                        len,
+                        first_sample,
+                        samples: count,
+                    });
+                }
+                data_offset += len;
+            }
+        }
+        Ok(())
+    }
+
+    fn media_duration(&self) -> u64 {
+        self.samples.iter().map(|s| s.duration as u64).sum()
+    }
+}
+
+fn build_moov(tracks: &[Track], co64: bool) -> Result<Vec<u8>> {
+    let first_moov = payload(&tracks[0].moov)?;
+    let mut mvhd = payload(find(first_moov, b"mvhd")?)?.to_vec();
+    let movie_timescale = read_u32(&mvhd, full_offset(&mvhd, 12, 20)?)?.max(1);
+    let movie_duration = tracks
+        .iter()
+        .map(|t| t.media_duration() * movie_timescale as u64 / t.timescale.max(1) as u64)
+        .max()
+        .unwrap_or_default();
+    set_duration(&mut mvhd, 16, 24, movie_duration)?;
+    let next_track_id = mvhd.len() - 4;
+    mvhd[next_track_id..].copy_from_slice(&(tracks.len() as u32 + 1).to_be_bytes());
+
+    let mut moov = make_box(b"mvhd", &mvhd);
+    for (index, track) in tracks.iter().enumerate() {
+        let track_duration =
+            track.media_duration() * movie_timescale as u64 / track.timescale.max(1) as u64;
+        moov.extend(build_trak(track, index as u32 + 1, track_duration, co64)?);
+    }
+    Ok(make_box(b"moov", &moov))
+}
+
+fn build_trak(track: &Track, track_id: u32, track_duration: u64, co64: bool) -> Result<Vec<u8>> {
+    let mut trak = vec![];
+    for (typ, full, data) in children(payload(&track.trak)?)? {
+        match &typ {
+            b"tkhd" => {
+                let mut tkhd = data.to_vec();
+                let id_offset = full_offset(&tkhd, 12, 20)?;
+                if tkhd.len() < id_offset + 4 {
+                    return Err(Error::Format("tkhd长度错误".to_string()));
+                }
+                // track_enabled | track_in_movie
+                tkhd[3] |= 0x03;
+                tkhd[id_offset..id_offset + 4].copy_from_slice(&track_id.to_be_bytes());
+                set_duration(&mut tkhd, 20, 28, track_duration)?;
+                trak.extend(make_box(b"tkhd", &tkhd));
+            }
+            b"mdia" => {
+                let mut mdia = vec![];
+                for (typ, full, data) in children(data)? {
+                    match &typ {
+                        b"mdhd" => {
+                            let mut mdhd = data.to_vec();
+                            set_duration(&mut mdhd, 16, 24, track.media_duration())?;
+                            mdia.extend(make_box(b"mdhd", &mdhd));
+                        }
+                        b"minf" => {
+                            let mut minf = vec![];
+                            for (typ, full, data) in children(data)? {
+                                if &typ == b"stbl" {
+                                    minf.extend(build_stbl(track, data, co64)?);
+                                } else {
+                                    minf.extend_from_slice(full);
+                                }
+                            }
+                            mdia.extend(make_box(b"minf", &minf));
+                        }
+                        _ => mdia.extend_from_slice(full),
+                    }
+                }
+                trak.extend(make_box(b"mdia", &mdia));
+            }
+            // 编辑列表对应分片中的时长, 与重建后的样本表不一致
+            b"edts" => {}
+            _ => trak.extend_from_slice(full),
+        }
+    }
+    Ok(make_box(b"trak", &trak))
+}
+
+fn build_stbl(track: &Track, stbl: &[u8], co64: bool) -> Result<Vec<u8>> {
+    let mut out = find(stbl, b"stsd")?.to_vec();
+
+    // stts: 相同时长的连续样本合并为一项
+    let durations = run_length(track.samples.iter().map(|s| s.duration));
+    out.extend(make_table(b"stts", 0, &durations, |(count, duration)| {
+        [count.to_be_bytes(), duration.to_be_bytes()].concat()
+    }));
+
+    if track.samples.iter().any(|s| s.cto != 0) {
+        let version = if track.samples.iter().any(|s| s.cto < 0) {
+            1
+        } else {
+            0
+        };
+        let offsets = run_length(track.samples.iter().map(|s| s.cto));
+        out.extend(make_table(b"ctts", version, &offsets, |(count, cto)| {
+            [count.to_be_bytes(), cto.to_be_bytes()].concat()
+        }));
+    }
+
+    if &track.handler == b"vide" && track.samples.iter().any(|s| s.flags & SAMPLE_NON_SYNC != 0) {
+        let sync: Vec<u32> = (0..track.samples.len())
+            .filter(|i| track.samples[*i].flags & SAMPLE_NON_SYNC == 0)
+            .map(|i| i as u32 + 1)
+            .collect();
+        out.extend(make_table(b"stss", 0, &sync, |index| {
+            index.to_be_bytes().to_vec()
+        }));
+    }
+
+    // stsc: 每个trun为一个chunk, 样本数相同的连续chunk合并为一项
+    let mut stsc: Vec<(u32, u32)> = vec![];
+    for (index, run) in track.runs.iter().enumerate() {
+        if stsc.last().map(|(_, samples)| *samples) != Some(run.samples as u32) {
+            stsc.push((index as u32 + 1, run.samples as u32));
+        }
+    }
+    out.extend(make_table(b"stsc", 0, &stsc, |(first_chunk, samples)| {
+        [
+            first_chunk.to_be_bytes(),
+            samples.to_be_bytes(),
+            1u32.to_be_bytes(),
+        ]
+        .concat()
+    }));
+
+    let mut stsz = [0u32.to_be_bytes(), 0u32.to_be_bytes()].concat();
+    stsz.extend((track.samples.len() as u32).to_be_bytes());
+    for sample in &track.samples {
+        stsz.extend(sample.size.to_be_bytes());
+    }
+    out.extend(make_box(b"stsz", &stsz));
+
+    if co64 {
+        out.extend(make_table(b"co64", 0, &track.runs, |run| {
+            run.output_offset.to_be_bytes().to_vec()
+        }));
+    } else {
+        out.extend(make_table(b"stco", 0, &track.runs, |run| {
+            (run.output_offset as u32).to_be_bytes().to_vec()
+        }));
+    }
+    Ok(make_box(b"stbl", &out))
+}
+
+fn run_length<T: PartialEq + Copy>(values: impl Iterator<Item = T>) -> Vec<(u32, T)> {
+    let mut runs: Vec<(u32, T)> = vec![];
+    for value in values {
+        match runs.last_mut() {
+            Some((count, last)) if *last == value => *count += 1,
+            _ => runs.push((1, value)),
+        }
+    }
+    runs
+}
+
+/// 构造 version/flags + entry_count + entries 形式的表
+fn make_table<T>(
+    typ: &[u8; 4],
+    version: u8,
+    entries: &[T],
+    entry: impl Fn(&T) -> Vec<u8>,
+) -> Vec<u8> {
+    let mut data = vec![version, 0, 0, 0];
+    data.extend((entries.len() as u32).to_be_bytes());
+    for item in entries {
+        data.extend(entry(item));
+    }
+    make_box(typ, &data)
+}
+
+fn make_box(typ: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(payload.len() + 8);
+    data.extend((payload.len() as u32 + 8).to_be_bytes());
+    data.extend_from_slice(typ);
+    data.extend_from_slice(payload);
+    data
+}
+
+/// 读取文件中位于offset的box头, 返回类型与box大小
+fn read_box_header(
+    reader: &mut (impl Read + Seek),
+    offset: u64,
+    end: u64,
+) -> Result<Option<([u8; 4], u64)>> {
+    if offset + 8 > end {
+        return Ok(None);
+    }
+    let mut header = [0u8; 8];
+    reader.seek(SeekFrom::Start(offset))?;
+    reader.read_exact(&mut header)?;
+    let typ: [u8; 4] = header[4..8].try_into().unwrap();
+    let (header_len, size) = match u32::from_be_bytes(header[0..4].try_into().unwrap()) {
+        0 => (8, end - offset),
+        1 => {
+            let mut large = [0u8; 8];
+            reader.read_exact(&mut large)?;
+            (16, u64::from_be_bytes(large))
+        }
+        size => (8, size as u64),
+    };
+    if size < header_len || offset + size > end {
+        return Err(Error::Format(format!(
+            "box大小错误: {} @ {}",
+            String::from_utf8_lossy(&typ),
+            offset
+        )));
+    }
+    Ok(Some((typ, size)))
+}
+
+/// 内存中的box: 类型, 完整的box, box的内容
+type BoxRef<'a> = ([u8; 4], &'a [u8], &'a [u8]);
+
+/// 拆分内存中的box序列
+fn children(data: &[u8]) -> Result<Vec<BoxRef<'_>>> {
+    let mut boxes = vec![];
+    let mut offset = 0;
+    while offset + 8 <= data.len() {
+        let typ: [u8; 4] = data[offset + 4..offset + 8].try_into().unwrap();
+        let (header_len, size) = match read_u32(data, offset)? {
+            0 => (8, data.len() - offset),
+            1 => (16, read_u64(data, offset + 8)? as usize),
+            size => (8, size as usize),
+        };
+        if size < header_len || offset + size > data.len() {
+            return Err(Error::Format(format!(
+                "box大小错误: {}",
+                String::from_utf8_lossy(&typ)
+            )));
+        }
+        boxes.push((
+            typ,
+            &data[offset..offset + size],
+            &data[offset + header_len..offset + size],
+        ));
+        offset += size;
+    }
+    Ok(boxes)
+}
+
+/// 完整box的内容
+fn payload(data: &[u8]) -> Result<&[u8]> {
+    children(data)?
+        .into_iter()
+        .next()
+        .map(|(_, _, payload)| payload)
+        .ok_or_else(|| Error::Format("box格式错误".to_string()))
+}
+
+/// 在box序列中查找指定类型的box, 返回完整的box
+fn find<'a>(data: &'a [u8], typ: &[u8; 4]) -> Result<&'a [u8]> {
+    children(data)?
+        .into_iter()
+        .find(|(t, _, _)| t == typ)
+        .map(|(_, full, _)| full)
+        .ok_or_else(|| Error::Format(format!("缺少{}", String::from_utf8_lossy(typ))))
+}
+
+/// full box中字段的位置, 根据version选择v0/v1的偏移量
+fn full_offset(data: &[u8], v0: usize, v1: usize) -> Result<usize> {
+    match data.first() {
+        Some(0) => Ok(v0),
+        Some(1) => Ok(v1),
+        _ => Err(Error::Format("不支持的box版本".to_string())),
+    }
+}
+
+/// 写入duration, v0为32位, v1为64位
+fn set_duration(data: &mut [u8], v0: usize, v1: usize, duration: u64) -> Result<()> {
+    let offset = full_offset(data, v0, v1)?;
+    let version = data[0];
+    let len = if version == 1 { 8 } else { 4 };
+    let field = data
+        .get_mut(offset..offset + len)
+        .ok_or_else(|| Error::Format("box长度错误".to_string()))?;
+    if version == 1 {
+        field.copy_from_slice(&duration.to_be_bytes());
+    } else {
+        field.copy_from_slice(&(duration.min(u32::MAX as u64) as u32).to_be_bytes());
+    }
+    Ok(())
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_be_bytes(b.try_into().unwrap()))
+        .ok_or_else(|| Error::Format("box长度错误".to_string()))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Result<u64> {
+    data.get(offset..offset + 8)
+        .map(|b| u64::from_be_bytes(b.try_into().unwrap()))
+        .ok_or_else(|| Error::Format("box长度错误".to_string()))
+}
+
+/// 分段信息
+impl SegmentBase {
+    /// 初始化段 (ftyp+moov) 的字节范围, 闭区间
+    pub fn initialization_range(&self) -> Option<(u64, u64)> {
+        parse_range(&self.initialization)
+    }
+
+    /// 索引 (sidx) 的字节范围, 闭区间
+    pub fn index_range_bounds(&self) -> Option<(u64, u64)> {
+        parse_range(&self.index_range)
+    }
+}
+
+fn parse_range(range: &str) -> Option<(u64, u64)> {
+    let (start, end) = range.split_once('-')?;
+    let (start, end) = (start.trim().parse().ok()?, end.trim().parse().ok()?);
+    if start > end {
+        return None;
+    }
+    Some((start, end))
+}
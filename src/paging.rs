@@ -0,0 +1,154 @@
+//! 自动翻页
+//!
+//! 先请求第一页获得总数, 再并发请求剩余的页, 按顺序逐条返回
+//! 出现错误时返回该错误并结束
+
+use std::future::Future;
+
+use futures::stream::{self, BoxStream};
+use futures::{StreamExt, TryStreamExt};
+
+use crate::{Client, FavListMedia, FavListOrder, Result, VideoArchive};
+
+/// 翻页的设置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageOptions {
+    page_size: Option<i64>,
+    concurrency: usize,
+}
+
+impl Default for PageOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PageOptions {
+    /// 构造方法, 默认使用各接口建议的每页数量, 2个并发
+    pub fn new() -> Self {
+        Self {
+            page_size: None,
+            concurrency: 2,
+        }
+    }
+
+    /// 每页的数量
+    pub fn page_size(mut self, page_size: i64) -> Self {
+        self.page_size = Some(page_size.max(1));
+        self
+    }
+
+    /// 同时请求的页数
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+}
+
+/// 一页的内容, total为总条数
+pub(crate) struct Page<T> {
+    items: Vec<T>,
+    total: i64,
+}
+
+/// 按页请求, fetch的参数为页码 (从1开始)
+pub(crate) fn paginate<'a, T, F, Fut>(
+    page_size: i64,
+    concurrency: usize,
+    fetch: F,
+) -> BoxStream<'a, Result<T>>
+where
+    T: Send + 'a,
+    F: Fn(i64) -> Fut + Send + Sync + 'a,
+    Fut: Future<Output = Result<Page<T>>> + Send + 'a,
+{
+    stream::once(async move {
+        let first: Page<T> = fetch(1).await?;
+        let pages = (first.total.max(0) as u64).div_ceil(page_size as u64) as i64;
+        let rest = stream::iter(2..=pages).map(fetch).buffered(concurrency);
+        Result::Ok(stream::iter(vec![Ok(first)]).chain(rest))
+    })
+    .try_flatten()
+    .map_ok(|page| stream::iter(page.items.into_iter().map(Ok)))
+    .try_flatten()
+    .scan(false, |failed, item| {
+        if *failed {
+            return futures::future::ready(None);
+        }
+        *failed = item.is_err();
+        futures::future::ready(Some(item))
+    })
+    .boxed()
+}
+
+/// 自动翻页
+impl Client {
+    /// 收藏夹中的所有视频, 默认每页20条
+    pub fn fav_list_stream(
+        &self,
+        fid: i64,
+        keyword: Option<String>,
+        order: FavListOrder,
+        options: PageOptions,
+    ) -> BoxStream<'_, Result<FavListMedia>> {
+        let page_size = options.page_size.unwrap_or(20);
+        paginate(page_size, options.concurrency, move |page_num| {
+            let keyword = keyword.clone();
+            async move {
+                let page = self
+                    .fav_list_page(fid, page_num, page_size, keyword, order)
+                    .await?;
+                // 没有更多时不再请求后面的页
+                let total = if page.has_more {
+                    page.info.media_count
+                } else {
+                    (page_num - 1) * page_size + page.medias.len() as i64
+                };
+                Ok(Page {
+                    items: page.medias,
+                    total,
+                })
+            }
+        })
+    }
+
+    /// 合集中的所有视频, 默认每页30条
+    pub fn collection_videos_stream(
+        &self,
+        mid: i64,
+        sid: i64,
+        sort_reverse: bool,
+        options: PageOptions,
+    ) -> BoxStream<'_, Result<VideoArchive>> {
+        let page_size = options.page_size.unwrap_or(30);
+        paginate(page_size, options.concurrency, move |page_num| async move {
+            let page = self
+                .collection_video_page(mid, sid, sort_reverse, page_num, page_size)
+                .await?;
+            Ok(Page {
+                items: page.archives,
+                total: page.page.total,
+            })
+        })
+    }
+
+    /// 系列中的所有视频, 默认每页30条
+    pub fn series_videos_stream(
+        &self,
+        mid: i64,
+        series_id: i64,
+        sort_reverse: bool,
+        options: PageOptions,
+    ) -> BoxStream<'_, Result<VideoArchive>> {
+        let page_size = options.page_size.unwrap_or(30);
+        paginate(page_size, options.concurrency, move |page_num| async move {
+            let page = self
+                .series_video_page(mid, series_id, sort_reverse, page_num, page_size)
+                .await?;
+            Ok(Page {
+                items: page.archives,
+                total: page.page.total,
+            })
+        })
+    }
+}
@@ -0,0 +1,359 @@
+//! 取流参数
+//!
+//! fnval为位标志, MP4与DASH互斥, HDR/4K/杜比/8K/AV1只能与DASH一起使用
+//! 4K与8K还需要fourk=1
+
+use std::ops::{BitOr, BitOrAssign};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    web, Client, Error, PgcPlayUrlV2, Result, VideoQuality, VideoUrl, FNVAL_DASH, FNVAL_DASH_4K,
+    FNVAL_DASH_8K, FNVAL_DASH_AV1, FNVAL_DASH_DB, FNVAL_DASH_HDR, FNVAL_DASH_VISION, FNVAL_FLV,
+    FNVAL_MP4, VIDEO_QUALITY_720P,
+};
+
+/// 取流格式 (fnval)
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Fnval(i64);
+
+impl Fnval {
+    /// FLV格式, 可能被分段
+    pub const fn flv() -> Self {
+        Fnval(FNVAL_FLV)
+    }
+
+    /// MP4格式, 仅支持240P/360P/480P/720P (html5平台可以使用高画质)
+    pub const fn mp4() -> Self {
+        Fnval(FNVAL_MP4)
+    }
+
+    /// DASH格式
+    pub const fn dash() -> Self {
+        Fnval(FNVAL_DASH)
+    }
+
+    /// 使用位标志构造, 不检查是否合法
+    pub const fn from_bits(bits: i64) -> Self {
+        Fnval(bits)
+    }
+
+    pub const fn bits(&self) -> i64 {
+        self.0
+    }
+
+    /// HDR 真彩色
+    pub const fn with_hdr(self) -> Self {
+        Fnval(self.0 | FNVAL_DASH_HDR)
+    }
+
+    /// 4K 超清, 需要fourk=1
+    pub const fn with_4k(self) -> Self {
+        Fnval(self.0 | FNVAL_DASH_4K)
+    }
+
+    /// 杜比全景声音频
+    pub const fn with_dolby_audio(self) -> Self {
+        Fnval(self.0 | FNVAL_DASH_DB)
+    }
+
+    /// 杜比视界
+    pub const fn with_dolby_vision(self) -> Self {
+        Fnval(self.0 | FNVAL_DASH_VISION)
+    }
+
+    /// 8K 超高清, 需要fourk=1
+    pub const fn with_8k(self) -> Self {
+        Fnval(self.0 | FNVAL_DASH_8K)
+    }
+
+    /// AV1编码
+    pub const fn with_av1(self) -> Self {
+        Fnval(self.0 | FNVAL_DASH_AV1)
+    }
+
+    /// DASH格式, 并包含所有可选的标志
+    pub const fn all() -> Self {
+        Self::dash()
+            .with_hdr()
+            .with_4k()
+            .with_dolby_audio()
+            .with_dolby_vision()
+            .with_8k()
+            .with_av1()
+    }
+
+    /// 取指定画质需要的最小fnval
+    pub fn for_quality(quality: VideoQuality) -> Self {
+        match quality.fnval() {
+            FNVAL_FLV => Self::dash(),
+            bits => Fnval(bits),
+        }
+    }
+
+    /// 是否包含所有指定的标志
+    pub const fn contains(&self, bits: i64) -> bool {
+        self.0 & bits == bits
+    }
+
+    pub const fn is_dash(&self) -> bool {
+        self.contains(FNVAL_DASH)
+    }
+
+    pub const fn is_mp4(&self) -> bool {
+        self.contains(FNVAL_MP4)
+    }
+
+    pub const fn is_flv(&self) -> bool {
+        self.0 == FNVAL_FLV
+    }
+
+    /// 是否需要fourk=1
+    pub const fn need_fourk(&self) -> bool {
+        self.0 & (FNVAL_DASH_4K | FNVAL_DASH_8K) != 0
+    }
+
+    /// 检查标志的组合以及是否能取到指定的画质
+    pub fn validate(&self, quality: VideoQuality) -> Result<()> {
+        if self.is_mp4() && self.is_dash() {
+            return Err(Error::InvalidArgument(format!(
+                "fnval={}: MP4与DASH不能同时使用",
+                self.0
+            )));
+        }
+        let dash_only = FNVAL_DASH_HDR
+            | FNVAL_DASH_4K
+            | FNVAL_DASH_DB
+            | FNVAL_DASH_VISION
+            | FNVAL_DASH_8K
+            | FNVAL_DASH_AV1;
+        if !self.is_dash() && self.0 & dash_only != 0 {
+            return Err(Error::InvalidArgument(format!(
+                "fnval={}: 画质/编码标志只能与DASH一起使用",
+                self.0
+            )));
+        }
+        let required = quality.fnval();
+        if !self.contains(required) {
+            return Err(Error::InvalidArgument(format!(
+                "画质{}需要fnval包含{}, 当前为{}",
+                quality, required, self.0
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl From<i64> for Fnval {
+    fn from(bits: i64) -> Self {
+        Fnval(bits)
+    }
+}
+
+impl From<Fnval> for i64 {
+    fn from(fnval: Fnval) -> Self {
+        fnval.0
+    }
+}
+
+impl BitOr for Fnval {
+    type Output = Fnval;
+
+    fn bitor(self, rhs: Fnval) -> Fnval {
+        Fnval(self.0 | rhs.0)
+    }
+}
+
+impl BitOr<i64> for Fnval {
+    type Output = Fnval;
+
+    fn bitor(self, rhs: i64) -> Fnval {
+        Fnval(self.0 | rhs)
+    }
+}
+
+impl BitOrAssign for Fnval {
+    fn bitor_assign(&mut self, rhs: Fnval) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl std::fmt::Display for Fnval {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// 取流请求, 普通视频使用bvid, 番剧/电影 (PGC) 使用ep_id
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayUrlRequest {
+    bvid: String,
+    ep_id: Option<i64>,
+    cid: i64,
+    quality: VideoQuality,
+    fnval: Fnval,
+    fnver: i64,
+    fourk: bool,
+    platform: String,
+    high_quality: bool,
+}
+
+impl PlayUrlRequest {
+    /// 构造方法, 默认720P, DASH格式, fourk=1, pc平台
+    pub fn new(bvid: impl Into<String>, cid: i64) -> Self {
+        Self {
+            bvid: bvid.into(),
+            ep_id: None,
+            cid,
+            quality: VIDEO_QUALITY_720P,
+            fnval: Fnval::dash(),
+            fnver: 0,
+            fourk: true,
+            platform: "pc".to_string(),
+            high_quality: false,
+        }
+    }
+
+    /// PGC的取流请求, 默认值与new相同
+    pub fn ep(ep_id: i64, cid: i64) -> Self {
+        let mut request = Self::new("", cid);
+        request.ep_id = Some(ep_id);
+        request
+    }
+
+    /// 画质 (qn)
+    pub fn quality(mut self, quality: VideoQuality) -> Self {
+        self.quality = quality;
+        self
+    }
+
+    /// 取流格式
+    pub fn fnval(mut self, fnval: impl Into<Fnval>) -> Self {
+        self.fnval = fnval.into();
+        self
+    }
+
+    /// 画质并使用该画质需要的最小fnval
+    pub fn quality_with_fnval(self, quality: VideoQuality) -> Self {
+        self.quality(quality).fnval(Fnval::for_quality(quality))
+    }
+
+    /// 目前只能为0
+    pub fn fnver(mut self, fnver: i64) -> Self {
+        self.fnver = fnver;
+        self
+    }
+
+    /// 是否允许4K/8K
+    pub fn fourk(mut self, fourk: bool) -> Self {
+        self.fourk = fourk;
+        self
+    }
+
+    /// 平台, pc: web播放 (需要Referer); html5: 移动端网页 (MP4地址不需要Referer)
+    pub fn platform(mut self, platform: impl Into<String>) -> Self {
+        self.platform = platform.into();
+        self
+    }
+
+    /// html5平台时获取高画质的MP4
+    pub fn high_quality(mut self, high_quality: bool) -> Self {
+        self.high_quality = high_quality;
+        self
+    }
+
+    /// 检查fnval与画质以及fourk是否匹配
+    pub fn validate(&self) -> Result<()> {
+        self.fnval.validate(self.quality)?;
+        if !self.fourk && (self.fnval.need_fourk() || self.quality.need_fourk()) {
+            return Err(Error::InvalidArgument(format!(
+                "画质{}需要fourk=1",
+                self.quality
+            )));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn query(&self) -> serde_json::Value {
+        let mut query = serde_json::json!({
+            "cid": self.cid,
+            "qn": self.quality.code,
+            "fnval": self.fnval,
+            "fnver": self.fnver,
+            "fourk": self.fourk as i64,
+            "platform": self.platform,
+        });
+        if !self.bvid.is_empty() {
+            query["bvid"] = serde_json::json!(self.bvid);
+        }
+        if let Some(ep_id) = self.ep_id {
+            query["ep_id"] = serde_json::json!(ep_id);
+        }
+        if self.high_quality {
+            query["high_quality"] = serde_json::json!(1);
+        }
+        query
+    }
+}
+
+/// 取流
+impl Client {
+    /// 获取下载地址, 请求前会检查参数 (PlayUrlRequest::validate)
+    pub async fn play_url(&self, request: &PlayUrlRequest) -> Result<VideoUrl> {
+        request.validate()?;
+        self.request_play_url(request).await
+    }
+
+    /// 获取番剧/电影 (PGC) 的下载地址
+    /// 未购买或地区限制时只能获取到试看片段 (VideoUrl::is_preview)
+    pub async fn pgc_play_url(&self, request: &PlayUrlRequest) -> Result<VideoUrl> {
+        request.validate()?;
+        self.request_api(
+            reqwest::Method::GET,
+            "/pgc/player/web/playurl",
+            Some(request.query()),
+            None,
+        )
+        .await
+    }
+
+    /// 获取番剧/电影 (PGC) 的下载地址 (v2接口)
+    pub async fn pgc_play_url_v2(&self, request: &PlayUrlRequest) -> Result<VideoUrl> {
+        request.validate()?;
+        let result: PgcPlayUrlV2 = self
+            .request_api(
+                reqwest::Method::GET,
+                "/pgc/player/web/v2/playurl",
+                Some(request.query()),
+                None,
+            )
+            .await?;
+        Ok(result.video_info)
+    }
+
+    /// 获取剧集的下载地址, 剧集来自videos_info返回的SsState
+    pub async fn ep_download_url(
+        &self,
+        ep: &web::Ep,
+        fnval: impl Into<Fnval>,
+        video_quality: VideoQuality,
+    ) -> Result<VideoUrl> {
+        self.pgc_play_url(
+            &PlayUrlRequest::ep(ep.ep_id, ep.cid)
+                .quality(video_quality)
+                .fnval(fnval),
+        )
+        .await
+    }
+
+    pub(crate) async fn request_play_url(&self, request: &PlayUrlRequest) -> Result<VideoUrl> {
+        self.request_api_wbi(
+            reqwest::Method::GET,
+            "/x/player/wbi/playurl",
+            Some(request.query()),
+            None,
+        )
+        .await
+    }
+}
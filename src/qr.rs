@@ -0,0 +1,161 @@
+//! 登录二维码渲染 (需要开启qr特性)
+
+use qrcode::render::{svg, unicode};
+use qrcode::{Color, QrCode};
+
+use crate::{Error, LoginQrData, Result, TvLoginQrData, WebQrData};
+
+/// 二维码四周留白的模块数
+const QUIET_ZONE: usize = 4;
+
+fn qr_code(data: &str) -> Result<QrCode> {
+    QrCode::new(data.as_bytes()).map_err(|err| Error::InvalidArgument(err.to_string()))
+}
+
+/// 使用Unicode半块字符渲染二维码, 每个字符表示上下两个模块
+/// 按深色背景的终端反色输出, 浅色背景的终端请使用qr_to_ansi
+pub fn qr_to_unicode(data: &str) -> Result<String> {
+    Ok(qr_code(data)?
+        .render::<unicode::Dense1x2>()
+        .dark_color(unicode::Dense1x2::Light)
+        .light_color(unicode::Dense1x2::Dark)
+        .build())
+}
+
+/// 使用ANSI颜色与半块字符渲染二维码, 固定为白底黑码, 不受终端配色影响
+pub fn qr_to_ansi(data: &str) -> Result<String> {
+    let code = qr_code(data)?;
+    let width = code.width();
+    let colors = code.to_colors();
+    let size = width + QUIET_ZONE * 2;
+    let dark = |x: usize, y: usize| {
+        x >= QUIET_ZONE
+            && y >= QUIET_ZONE
+            && x < width + QUIET_ZONE
+            && y < width + QUIET_ZONE
+            && colors[(y - QUIET_ZONE) * width + x - QUIET_ZONE] == Color::Dark
+    };
+    let mut out = String::new();
+    for y in (0..size).step_by(2) {
+        for x in 0..size {
+            // 前景色为上半块, 背景色为下半块
+            let fg = if dark(x, y) { 30 } else { 97 };
+            let bg = if y + 1 < size && dark(x, y + 1) {
+                40
+            } else {
+                107
+            };
+            out.push_str(&format!("\x1b[{};{}m▀", fg, bg));
+        }
+        out.push_str("\x1b[0m\n");
+    }
+    Ok(out)
+}
+
+/// 渲染为SVG
+pub fn qr_to_svg(data: &str) -> Result<String> {
+    Ok(qr_code(data)?
+        .render::<svg::Color>()
+        .min_dimensions(200, 200)
+        .build())
+}
+
+/// 渲染为PNG (8位灰度), module_size为每个模块的像素数
+pub fn qr_to_png(data: &str, module_size: u32) -> Result<Vec<u8>> {
+    if module_size == 0 {
+        return Err(Error::InvalidArgument("module_size不能为0".to_string()));
+    }
+    let code = qr_code(data)?;
+    let width = code.width();
+    let colors = code.to_colors();
+    let scale = module_size as usize;
+    let size = (width + QUIET_ZONE * 2) * scale;
+    let mut pixels = vec![0xFFu8; size * size];
+    for (i, color) in colors.iter().enumerate() {
+        if *color != Color::Dark {
+            continue;
+        }
+        let (x, y) = (i % width + QUIET_ZONE, i / width + QUIET_ZONE);
+        for row in y * scale..(y + 1) * scale {
+            pixels[row * size + x * scale..row * size + (x + 1) * scale].fill(0);
+        }
+    }
+    let mut buf = Vec::new();
+    let mut encoder = png::Encoder::new(&mut buf, size as u32, size as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&pixels))
+        .map_err(|err| Error::InvalidArgument(err.to_string()))?;
+    Ok(buf)
+}
+
+/// 二维码渲染
+impl LoginQrData {
+    /// 渲染为终端可显示的二维码 (Unicode半块字符)
+    pub fn qr_unicode(&self) -> Result<String> {
+        qr_to_unicode(&self.url)
+    }
+
+    /// 渲染为终端可显示的二维码 (ANSI颜色)
+    pub fn qr_ansi(&self) -> Result<String> {
+        qr_to_ansi(&self.url)
+    }
+
+    /// 渲染为SVG
+    pub fn qr_svg(&self) -> Result<String> {
+        qr_to_svg(&self.url)
+    }
+
+    /// 渲染为PNG
+    pub fn qr_png(&self, module_size: u32) -> Result<Vec<u8>> {
+        qr_to_png(&self.url, module_size)
+    }
+}
+
+/// 二维码渲染
+impl WebQrData {
+    /// 渲染为终端可显示的二维码 (Unicode半块字符)
+    pub fn qr_unicode(&self) -> Result<String> {
+        qr_to_unicode(&self.url)
+    }
+
+    /// 渲染为终端可显示的二维码 (ANSI颜色)
+    pub fn qr_ansi(&self) -> Result<String> {
+        qr_to_ansi(&self.url)
+    }
+
+    /// 渲染为SVG
+    pub fn qr_svg(&self) -> Result<String> {
+        qr_to_svg(&self.url)
+    }
+
+    /// 渲染为PNG
+    pub fn qr_png(&self, module_size: u32) -> Result<Vec<u8>> {
+        qr_to_png(&self.url, module_size)
+    }
+}
+
+/// 二维码渲染
+impl TvLoginQrData {
+    /// 渲染为终端可显示的二维码 (Unicode半块字符)
+    pub fn qr_unicode(&self) -> Result<String> {
+        qr_to_unicode(&self.url)
+    }
+
+    /// 渲染为终端可显示的二维码 (ANSI颜色)
+    pub fn qr_ansi(&self) -> Result<String> {
+        qr_to_ansi(&self.url)
+    }
+
+    /// 渲染为SVG
+    pub fn qr_svg(&self) -> Result<String> {
+        qr_to_svg(&self.url)
+    }
+
+    /// 渲染为PNG
+    pub fn qr_png(&self, module_size: u32) -> Result<Vec<u8>> {
+        qr_to_png(&self.url, module_size)
+    }
+}
@@ -0,0 +1,195 @@
+//! DASH流的选择
+
+use crate::{Audio, AudioQuality, Dash, Video, VideoCodec, VideoQuality};
+
+/// 选择DASH流时的偏好
+/// 画质优先, 同画质时按编码偏好选择, 最后选择码率最高的
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamPreference {
+    max_quality: Option<VideoQuality>,
+    codecs: Vec<VideoCodec>,
+    max_height: Option<i64>,
+    max_bandwidth: Option<i64>,
+    prefer_dolby: bool,
+    prefer_hi_res: bool,
+}
+
+impl Default for StreamPreference {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StreamPreference {
+    /// 构造方法, 默认不限制画质, 编码偏好为 AVC > HEVC > AV1 (兼容性最好)
+    pub fn new() -> Self {
+        Self {
+            max_quality: None,
+            codecs: vec![VideoCodec::Avc, VideoCodec::Hevc, VideoCodec::Av1],
+            max_height: None,
+            max_bandwidth: None,
+            prefer_dolby: false,
+            prefer_hi_res: false,
+        }
+    }
+
+    /// 最高画质
+    pub fn max_quality(mut self, max_quality: VideoQuality) -> Self {
+        self.max_quality = Some(max_quality);
+        self
+    }
+
+    /// 编码偏好, 靠前的优先, 未列出的编码排在最后
+    pub fn codecs(mut self, codecs: Vec<VideoCodec>) -> Self {
+        self.codecs = codecs;
+        self
+    }
+
+    /// 最大高度 (像素)
+    pub fn max_height(mut self, max_height: i64) -> Self {
+        self.max_height = Some(max_height);
+        self
+    }
+
+    /// 视频的最大码率 (bps)
+    pub fn max_bandwidth(mut self, max_bandwidth: i64) -> Self {
+        self.max_bandwidth = Some(max_bandwidth);
+        self
+    }
+
+    /// 存在杜比全景声音频时优先选择
+    pub fn prefer_dolby(mut self, prefer_dolby: bool) -> Self {
+        self.prefer_dolby = prefer_dolby;
+        self
+    }
+
+    /// 存在Hi-Res无损音频时优先选择 (优先于杜比全景声)
+    pub fn prefer_hi_res(mut self, prefer_hi_res: bool) -> Self {
+        self.prefer_hi_res = prefer_hi_res;
+        self
+    }
+
+    fn codec_rank(&self, video: &Video) -> usize {
+        // 越大越优先
+        match video
+            .codec()
+            .and_then(|codec| self.codecs.iter().position(|c| *c == codec))
+        {
+            Some(index) => self.codecs.len() - index,
+            None => 0,
+        }
+    }
+
+    /// 选择视频流, 没有满足限制的流时选择画质最低的流
+    pub fn select_video<'a>(&self, videos: &'a [Video]) -> Option<&'a Video> {
+        let candidates: Vec<&Video> = videos
+            .iter()
+            .filter(|video| match &self.max_quality {
+                Some(quality) => video.id <= quality.code,
+                None => true,
+            })
+            .filter(|video| match self.max_height {
+                Some(max_height) => video.height <= max_height,
+                None => true,
+            })
+            .filter(|video| match self.max_bandwidth {
+                Some(max_bandwidth) => video.bandwidth <= max_bandwidth,
+                None => true,
+            })
+            .collect();
+        if candidates.is_empty() {
+            return videos
+                .iter()
+                .min_by_key(|video| (video.id, video.bandwidth));
+        }
+        candidates
+            .into_iter()
+            .max_by_key(|video| (video.id, self.codec_rank(video), video.bandwidth))
+    }
+
+    /// 选择音频流
+    /// 杜比全景声/Hi-Res只在偏好时选择, 其余选择码率最高的
+    pub fn select_audio<'a>(&self, audios: &'a [Audio]) -> Option<&'a Audio> {
+        let find =
+            |quality: AudioQuality| audios.iter().find(|audio| audio.quality() == Some(quality));
+        if self.prefer_hi_res {
+            if let Some(audio) = find(AudioQuality::HiRes) {
+                return Some(audio);
+            }
+        }
+        if self.prefer_dolby {
+            if let Some(audio) = find(AudioQuality::Dolby) {
+                return Some(audio);
+            }
+        }
+        audios
+            .iter()
+            .filter(|audio| {
+                !matches!(
+                    audio.quality(),
+                    Some(AudioQuality::Dolby | AudioQuality::HiRes)
+                )
+            })
+            .max_by_key(|audio| audio.bandwidth)
+            .or_else(|| audios.iter().max_by_key(|audio| audio.bandwidth))
+    }
+
+    /// 选择视频流与音频流, 音频包括杜比音频与Hi-Res无损音频
+    pub fn select(&self, dash: &Dash) -> DashSelection {
+        let video = self.select_video(&dash.video).cloned();
+        let audio = self.select_audio(&dash.all_audio()).cloned();
+        DashSelection {
+            video_urls: video.as_ref().map(Video::urls).unwrap_or_default(),
+            audio_urls: audio.as_ref().map(Audio::urls).unwrap_or_default(),
+            video,
+            audio,
+        }
+    }
+}
+
+/// DASH流的选择结果, urls按 base_url, backup_url, backup_url2 的顺序排列, 用于依次重试
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct DashSelection {
+    pub video: Option<Video>,
+    pub audio: Option<Audio>,
+    pub video_urls: Vec<String>,
+    pub audio_urls: Vec<String>,
+}
+
+pub(crate) fn ordered_urls(
+    base_url: &str,
+    backup_url: &[String],
+    backup_url2: &[String],
+) -> Vec<String> {
+    let mut urls: Vec<String> = vec![];
+    for url in std::iter::once(base_url)
+        .chain(backup_url.iter().map(String::as_str))
+        .chain(backup_url2.iter().map(String::as_str))
+    {
+        if !url.is_empty() && !urls.iter().any(|u| u == url) {
+            urls.push(url.to_string());
+        }
+    }
+    urls
+}
+
+impl Video {
+    /// 所有下载地址 (去重), 主地址在前
+    pub fn urls(&self) -> Vec<String> {
+        ordered_urls(&self.base_url, &self.backup_url, &self.backup_url2)
+    }
+}
+
+impl Audio {
+    /// 所有下载地址 (去重), 主地址在前
+    pub fn urls(&self) -> Vec<String> {
+        ordered_urls(&self.base_url, &self.backup_url, &self.backup_url2)
+    }
+}
+
+impl Dash {
+    /// 按偏好选择视频流与音频流
+    pub fn select(&self, preference: &StreamPreference) -> DashSelection {
+        preference.select(self)
+    }
+}
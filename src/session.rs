@@ -0,0 +1,225 @@
+use std::collections::BTreeMap;
+
+use crate::{AppCookieInfo, TvToken, WebToken};
+
+pub const COOKIE_SESSDATA: &str = "SESSDATA";
+pub const COOKIE_BILI_JCT: &str = "bili_jct";
+pub const COOKIE_DEDEUSERID: &str = "DedeUserID";
+pub const COOKIE_DEDEUSERID_CKMD5: &str = "DedeUserID__ckMd5";
+pub const COOKIE_BUVID3: &str = "buvid3";
+pub const COOKIE_BUVID4: &str = "buvid4";
+
+/// 会话, 保存请求时携带的所有Cookie以及TV登录的令牌
+/// 服务器返回的Set-Cookie会自动更新到会话中
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct Session {
+    cookies: BTreeMap<String, String>,
+    expires: i64,
+    refresh_token: Option<String>,
+    tv_token: Option<TvToken>,
+}
+
+impl Session {
+    /// 构造方法
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 使用登录得到的token构造会话
+    pub fn from_web_token(token: &WebToken) -> Self {
+        let mut session = Self::new();
+        session.set_web_token(token);
+        session
+    }
+
+    /// 获取Cookie
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.cookies.get(name).map(String::as_str)
+    }
+
+    /// 设置Cookie
+    pub fn set(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.cookies.insert(name.into(), value.into());
+    }
+
+    /// 删除Cookie
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        self.cookies.remove(name)
+    }
+
+    /// 清空会话
+    pub fn clear(&mut self) {
+        self.cookies.clear();
+        self.expires = 0;
+        self.refresh_token = None;
+        self.tv_token = None;
+    }
+
+    /// 清除WEB登录的Cookie与refresh_token, 保留设备标识与TV登录的令牌
+    pub fn clear_web_login(&mut self) {
+        for name in [
+            COOKIE_SESSDATA,
+            COOKIE_BILI_JCT,
+            COOKIE_DEDEUSERID,
+            COOKIE_DEDEUSERID_CKMD5,
+        ] {
+            self.remove(name);
+        }
+        self.expires = 0;
+        self.refresh_token = None;
+    }
+
+    /// 所有的Cookie
+    pub fn cookies(&self) -> &BTreeMap<String, String> {
+        &self.cookies
+    }
+
+    /// 注入登录得到的token
+    pub fn set_web_token(&mut self, token: &WebToken) {
+        self.set(COOKIE_SESSDATA, token.sessdata.as_str());
+        self.set(COOKIE_BILI_JCT, token.bili_jct.as_str());
+        self.set(COOKIE_DEDEUSERID, token.dedeuserid.to_string());
+        self.set(COOKIE_DEDEUSERID_CKMD5, token.dedeuserid_ckmd5.as_str());
+        self.expires = token.expires;
+    }
+
+    /// 登录的token, 没有SESSDATA时为None
+    pub fn web_token(&self) -> Option<WebToken> {
+        Some(WebToken {
+            dedeuserid: self
+                .get(COOKIE_DEDEUSERID)
+                .and_then(|uid| uid.parse().ok())
+                .unwrap_or_default(),
+            dedeuserid_ckmd5: self
+                .get(COOKIE_DEDEUSERID_CKMD5)
+                .unwrap_or_default()
+                .to_string(),
+            sessdata: self.get(COOKIE_SESSDATA)?.to_string(),
+            bili_jct: self.get(COOKIE_BILI_JCT).unwrap_or_default().to_string(),
+            expires: self.expires,
+        })
+    }
+
+    /// SESSDATA
+    pub fn sessdata(&self) -> Option<&str> {
+        self.get(COOKIE_SESSDATA)
+    }
+
+    /// CSRF Token (bili_jct), 写操作需要携带
+    pub fn csrf(&self) -> Option<&str> {
+        self.get(COOKIE_BILI_JCT)
+    }
+
+    /// 登录用户的mid
+    pub fn mid(&self) -> Option<i64> {
+        self.get(COOKIE_DEDEUSERID).and_then(|uid| uid.parse().ok())
+    }
+
+    /// SESSDATA的过期时间 (秒级时间戳)
+    pub fn expires(&self) -> i64 {
+        self.expires
+    }
+
+    /// 设备标识 buvid3
+    pub fn buvid3(&self) -> Option<&str> {
+        self.get(COOKIE_BUVID3)
+    }
+
+    /// 设备标识 buvid4
+    pub fn buvid4(&self) -> Option<&str> {
+        self.get(COOKIE_BUVID4)
+    }
+
+    /// WEB登录时返回的refresh_token, 用于刷新Cookie
+    pub fn refresh_token(&self) -> Option<&str> {
+        self.refresh_token.as_deref()
+    }
+
+    /// 注入WEB登录时返回的refresh_token
+    pub fn set_refresh_token(&mut self, refresh_token: impl Into<String>) {
+        self.refresh_token = Some(refresh_token.into());
+    }
+
+    /// TV登录的令牌
+    pub fn tv_token(&self) -> Option<&TvToken> {
+        self.tv_token.as_ref()
+    }
+
+    /// 注入TV登录的令牌
+    pub fn set_tv_token(&mut self, tv_token: TvToken) {
+        self.tv_token = Some(tv_token);
+    }
+
+    /// 注入APP/TV登录返回的WEB Cookie
+    pub fn set_app_cookie_info(&mut self, cookie_info: &AppCookieInfo) {
+        for cookie in &cookie_info.cookies {
+            if cookie.name == COOKIE_SESSDATA {
+                self.expires = cookie.expires;
+            }
+            self.set(cookie.name.as_str(), cookie.value.as_str());
+        }
+    }
+
+    /// 请求头中的Cookie, 会话为空时为None
+    pub fn cookie_header(&self) -> Option<String> {
+        if self.cookies.is_empty() {
+            return None;
+        }
+        Some(
+            self.cookies
+                .iter()
+                .map(|(name, value)| format!("{}={}", name, value))
+                .collect::<Vec<String>>()
+                .join("; "),
+        )
+    }
+
+    /// 将响应头中的Set-Cookie更新到会话
+    pub fn update_from_headers(&mut self, headers: &reqwest::header::HeaderMap) {
+        for value in headers.get_all(reqwest::header::SET_COOKIE) {
+            if let Ok(value) = value.to_str() {
+                self.update_from_set_cookie(value);
+            }
+        }
+    }
+
+    /// 将一条Set-Cookie更新到会话
+    pub fn update_from_set_cookie(&mut self, set_cookie: &str) {
+        let mut parts = set_cookie.split(';');
+        let (name, value) = match parts.next().and_then(|pair| pair.split_once('=')) {
+            Some((name, value)) => (name.trim(), value.trim()),
+            None => return,
+        };
+        if name.is_empty() {
+            return;
+        }
+        let mut expires = None;
+        let mut removed = value.is_empty();
+        for attr in parts {
+            let (key, attr_value) = attr.split_once('=').unwrap_or((attr, ""));
+            let key = key.trim().to_ascii_lowercase();
+            let attr_value = attr_value.trim();
+            if key == "max-age" {
+                if let Ok(max_age) = attr_value.parse::<i64>() {
+                    removed |= max_age <= 0;
+                    expires = Some(chrono::Local::now().timestamp() + max_age);
+                }
+            } else if key == "expires" && expires.is_none() {
+                if let Ok(time) = chrono::DateTime::parse_from_rfc2822(attr_value) {
+                    removed |= time.timestamp() <= chrono::Local::now().timestamp();
+                    expires = Some(time.timestamp());
+                }
+            }
+        }
+        if removed {
+            self.remove(name);
+            return;
+        }
+        if name == COOKIE_SESSDATA {
+            if let Some(expires) = expires {
+                self.expires = expires;
+            }
+        }
+        self.set(name, value);
+    }
+}
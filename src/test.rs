@@ -1,4 +1,75 @@
-use crate::{sign_form, Client, FNVAL_DASH, VIDEO_QUALITY_720P};
+use std::sync::{Arc, Mutex};
+
+use futures::{StreamExt, TryStreamExt};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::{
+    encrypt_password, sign_form, Client, CredentialStore, Error, FileCredentialStore, Geetest,
+    GeetestResult, LoginKey, LoginTvQrInfo, QrLoginPoller, QrLoginState, Session, TvToken,
+    WebQrStatus, WebToken, FNVAL_DASH, VIDEO_QUALITY_720P,
+};
+
+/// 本地模拟服务器, 对所有请求返回相同的内容, 并记录收到的请求
+struct MockServer {
+    url: String,
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockServer {
+    async fn start(headers: &[(&str, &str)], body: &str) -> Self {
+        let mut response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n",
+            body.len()
+        );
+        for (name, value) in headers {
+            response.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        response.push_str("\r\n");
+        response.push_str(body);
+        Self::start_with(move |_| response.clone().into_bytes()).await
+    }
+
+    /// 使用handler根据请求生成响应
+    async fn start_with(handler: impl Fn(&str) -> Vec<u8> + Send + 'static) -> Self {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(vec![]));
+        let requests_clone = requests.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buf = vec![];
+                let mut chunk = [0u8; 1024];
+                let mut expected = usize::MAX;
+                while buf.len() < expected {
+                    let n = stream.read(&mut chunk).await.unwrap();
+                    if n == 0 {
+                        break;
+                    }
+                    buf.extend_from_slice(&chunk[..n]);
+                    if let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+                        let head = String::from_utf8_lossy(&buf[..end]).to_lowercase();
+                        let length = head
+                            .lines()
+                            .find_map(|line| line.strip_prefix("content-length: "))
+                            .map(|length| length.trim().parse().unwrap())
+                            .unwrap_or(0);
+                        expected = end + 4 + length;
+                    }
+                }
+                let request = String::from_utf8_lossy(&buf).to_string();
+                let response = handler(&request);
+                requests_clone.lock().unwrap().push(request);
+                stream.write_all(&response).await.unwrap();
+            }
+        });
+        Self { url, requests }
+    }
+
+    fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
 
 #[tokio::test]
 async fn test_sign_form() {
@@ -179,7 +250,7 @@ async fn test_user_info() {
 
 #[test]
 fn test_parse_user_info() {
-    let json = r###""###;
+    let json = r###"{"code":0,"message":"0","ttl":1,"data":{"mid":504934876,"name":"bilirust","sex":"保密","face":"https://i0.hdslb.com/bfs/face/member/noface.jpg","sign":"","rank":10000,"level":3,"jointime":0,"moral":70,"silence":0,"email_status":0,"tel_status":1,"identification":0,"vip":{"type":0,"status":0,"due_date":0,"vip_pay_type":0,"theme_type":0,"label":{"path":"","text":"","label_theme":"","text_color":"","bg_style":0,"bg_color":"","border_color":"","use_img_label":true,"img_label_uri_hans":"","img_label_uri_hant":"","img_label_uri_hans_static":"","img_label_uri_hant_static":""},"avatar_subscript":0,"nickname_color":"","role":0,"avatar_subscript_url":"","tv_vip_status":0,"tv_vip_pay_type":0},"pendant":{"pid":0,"name":"","image":"","expire":0,"image_enhance":"","image_enhance_frame":""},"nameplate":{"nid":0,"name":"","image":"","image_small":"","level":"","condition":""},"official":{"role":0,"title":"","desc":"","type":-1},"birthday":0,"is_tourist":0,"is_fake_account":0,"pin_prompting":0,"is_deleted":0,"in_reg_audit":0,"is_rip_user":false,"profession":{"id":0,"name":"","show_name":"","is_show":0,"category_one":""},"face_nft":0,"face_nft_new":0,"is_senior_member":0,"level_exp":{"current_level":3,"current_min":1500,"current_exp":2000,"next_exp":4500},"coins":10.5,"following":1,"follower":0}}"###;
     let info: crate::Response<crate::MyInfo> = serde_json::from_str(json).unwrap();
     println!("{:?}", info);
 }
@@ -212,3 +283,1835 @@ async fn test_seasons_series_list() {
         }
     };
 }
+
+#[test]
+fn test_error_from_code() {
+    assert!(matches!(
+        Error::from_code(-101, "账号未登录", 1),
+        Error::NotLogin { code: -101, .. }
+    ));
+    assert!(matches!(
+        Error::from_code(-352, "风控校验失败", 1),
+        Error::RiskControl { code: -352, .. }
+    ));
+    assert!(matches!(
+        Error::from_code(-412, "请求被拦截", 1),
+        Error::RiskControl { code: -412, .. }
+    ));
+    assert!(matches!(
+        Error::from_code(-404, "啥都木有", 1),
+        Error::NotFound { code: -404, .. }
+    ));
+    let err = Error::from_code(62002, "稿件不可见", 1);
+    assert!(matches!(err, Error::Api { .. }));
+    assert_eq!(err.code(), Some(62002));
+    assert_eq!(err.message(), Some("稿件不可见"));
+    assert_eq!(err.ttl(), Some(1));
+}
+
+#[test]
+fn test_error_decode_path() {
+    let err = crate::from_str::<crate::Response<crate::BvInfo>>(r#"{"code":0,"data":{"aid":"x"}}"#)
+        .unwrap_err();
+    match err {
+        Error::Decode { path, .. } => assert_eq!(path, "data.aid"),
+        err => panic!("{}", err),
+    }
+}
+
+#[tokio::test]
+async fn test_client_builder_hosts() {
+    let server = MockServer::start(&[], r#"{"code":-101,"message":"账号未登录","ttl":1}"#).await;
+    let client = Client::builder()
+        .api_host(&server.url)
+        .user_agent("bilirust-test")
+        .connect_timeout(std::time::Duration::from_secs(5))
+        .timeout(std::time::Duration::from_secs(10))
+        .build()
+        .unwrap();
+    match client.my_info().await {
+        Err(Error::NotLogin { code, .. }) => assert_eq!(code, -101),
+        other => panic!("{:?}", other.map(|info| info.mid)),
+    }
+    let requests = server.requests();
+    assert!(requests[0].starts_with("GET /x/space/myinfo "));
+    assert!(requests[0].contains("user-agent: bilirust-test"));
+}
+
+#[test]
+fn test_client_builder_invalid_proxy() {
+    assert!(matches!(
+        Client::builder().proxy("not a proxy").build(),
+        Err(Error::Transport(_))
+    ));
+}
+
+#[test]
+fn test_wbi_sign() {
+    let mixin_key = crate::wbi_mixin_key(
+        "7cd084941338484aae1ad9425b84077c",
+        "4932caff0ff746eab6f01bf08b70ac45",
+    )
+    .unwrap();
+    assert_eq!(mixin_key, "ea1db124af3c7062474693fa704f4ff8");
+    let signed = crate::wbi_sign(
+        serde_json::json!({"foo": "114", "bar": "514", "zab": 1919810}),
+        &mixin_key,
+        1702204169,
+    )
+    .unwrap();
+    assert_eq!(signed["wts"], "1702204169");
+    assert_eq!(signed["w_rid"], "8f6f2b5b3d485fe1886cec6a0be8c5d4");
+}
+
+#[tokio::test]
+async fn test_request_api_wbi() {
+    let server = MockServer::start(
+        &[],
+        r#"{"code":-101,"message":"账号未登录","ttl":1,"data":{"isLogin":false,"wbi_img":{"img_url":"https://i0.hdslb.com/bfs/wbi/7cd084941338484aae1ad9425b84077c.png","sub_url":"https://i0.hdslb.com/bfs/wbi/4932caff0ff746eab6f01bf08b70ac45.png"}}}"#,
+    )
+    .await;
+    let client = Client::builder().api_host(&server.url).build().unwrap();
+    let keys = client.wbi_keys().await.unwrap();
+    assert_eq!(keys.img_key, "7cd084941338484aae1ad9425b84077c");
+    assert_eq!(keys.sub_key, "4932caff0ff746eab6f01bf08b70ac45");
+    assert!(client.user_info(504934876).await.is_err());
+    let requests = server.requests();
+    // 密钥已缓存, 不会再次请求nav
+    assert_eq!(requests.len(), 2);
+    assert!(requests[1].starts_with("GET /x/space/wbi/acc/info?"));
+    assert!(requests[1].contains("w_rid="));
+    assert!(requests[1].contains("wts="));
+}
+
+#[test]
+fn test_session_set_cookie() {
+    let mut session = Session::from_web_token(&WebToken {
+        dedeuserid: 1,
+        dedeuserid_ckmd5: "2".to_string(),
+        sessdata: "4".to_string(),
+        bili_jct: "5".to_string(),
+        expires: 3,
+    });
+    session.update_from_set_cookie(
+        "SESSDATA=new%2Csess; Path=/; Domain=bilibili.com; Expires=Wed, 14 Apr 2100 09:45:16 GMT; HttpOnly; Secure",
+    );
+    session.update_from_set_cookie("buvid3=abc; path=/; domain=.bilibili.com");
+    session.update_from_set_cookie("DedeUserID__ckMd5=; Max-Age=0; Path=/");
+    assert_eq!(session.sessdata(), Some("new%2Csess"));
+    assert_eq!(session.expires(), 4111379116);
+    assert_eq!(session.csrf(), Some("5"));
+    assert_eq!(session.buvid3(), Some("abc"));
+    assert_eq!(session.get("DedeUserID__ckMd5"), None);
+    assert_eq!(
+        session.cookie_header().unwrap(),
+        "DedeUserID=1; SESSDATA=new%2Csess; bili_jct=5; buvid3=abc"
+    );
+}
+
+#[tokio::test]
+async fn test_session_cookies() {
+    let server = MockServer::start(
+        &[
+            ("Set-Cookie", "bili_jct=new_jct; Path=/"),
+            ("Set-Cookie", "buvid4=device; Path=/"),
+        ],
+        r#"{"code":-101,"message":"账号未登录","ttl":1}"#,
+    )
+    .await;
+    let mut client = Client::builder().api_host(&server.url).build().unwrap();
+    client.login_set_web_token(&WebToken {
+        dedeuserid: 1,
+        dedeuserid_ckmd5: "2".to_string(),
+        sessdata: "4".to_string(),
+        bili_jct: "5".to_string(),
+        expires: 3,
+    });
+    let _ = client.my_info().await;
+    assert!(server.requests()[0]
+        .contains("cookie: DedeUserID=1; DedeUserID__ckMd5=2; SESSDATA=4; bili_jct=5\r\n"));
+    assert_eq!(client.csrf().as_deref(), Some("new_jct"));
+    assert_eq!(client.session().buvid4(), Some("device"));
+    assert_eq!(client.web_token().unwrap().sessdata, "4");
+}
+
+#[test]
+fn test_credential_store() {
+    let path = std::env::temp_dir()
+        .join(format!("bilirust-test-{}", std::process::id()))
+        .join("credential.json");
+    let store = FileCredentialStore::new(&path);
+    store.clear().unwrap();
+    assert!(store.load().unwrap().is_none());
+
+    let mut client = Client::new();
+    client.login_set_web_token(&WebToken {
+        dedeuserid: 1,
+        dedeuserid_ckmd5: "2".to_string(),
+        sessdata: "4".to_string(),
+        bili_jct: "5".to_string(),
+        expires: chrono::Local::now().timestamp() + 3600,
+    });
+    let mut session = client.session();
+    session.set_tv_token(TvToken::from(&LoginTvQrInfo {
+        error_data: 0,
+        mid: 1,
+        access_token: "access".to_string(),
+        refresh_token: "refresh".to_string(),
+        expires_in: 3600,
+        cookie_info: None,
+    }));
+    client.set_session(session);
+    client.save_credential(&store).unwrap();
+
+    let mut restored = Client::new();
+    let credential = restored.load_credential(&store).unwrap().unwrap();
+    assert!(!credential.is_expired());
+    assert!(!credential.is_tv_expired());
+    assert_eq!(credential, client.credential());
+    assert_eq!(restored.credential(), client.credential());
+
+    let mut expired = credential;
+    expired.web_token.as_mut().unwrap().expires = 3;
+    assert!(expired.is_expired());
+    store.clear().unwrap();
+    std::fs::remove_dir(path.parent().unwrap()).unwrap();
+}
+
+#[tokio::test]
+async fn test_legacy_web_token_expires() {
+    let url = "https://passport.biligame.com/crossDomain?DedeUserID=1&DedeUserID__ckMd5=2&Expires=15551000&SESSDATA=4&bili_jct=5&gourl=http%3A%2F%2Fwww.bilibili.com";
+    let server = MockServer::start(
+        &[],
+        &format!(r#"{{"code":0,"status":true,"data":{{"url":"{}"}}}}"#, url),
+    )
+    .await;
+    let client = Client::builder()
+        .passport_host(&server.url)
+        .build()
+        .unwrap();
+    let now = chrono::Local::now().timestamp();
+    // 旧接口的Expires为有效秒数, 保存到会话时换算为时间戳
+    let info = client.login_qr_info("key".to_string()).await.unwrap();
+    assert_eq!(info.error_data, 0);
+    assert!(client.web_token().unwrap().expires >= now + 15551000);
+    assert!(!client.credential().is_expired());
+    // 解析方法仍返回原始值
+    let token = client.login_qr_info_parse_token(info.url).unwrap();
+    assert_eq!(token.expires, 15551000);
+    assert!(server.requests()[0].starts_with("POST /qrcode/getLoginInfo "));
+}
+
+#[test]
+fn test_correspond_path() {
+    let path = crate::correspond_path(1684466082517).unwrap();
+    assert_eq!(path.len(), 256);
+    assert!(path.chars().all(|c| c.is_ascii_hexdigit()));
+}
+
+#[tokio::test]
+async fn test_refresh_web_cookie() {
+    let passport = MockServer::start(
+        &[("Set-Cookie", "bili_jct=new_jct; Path=/")],
+        r#"{"code":0,"message":"0","ttl":1,"data":{"refresh":true,"timestamp":1684466082517,"status":0,"message":"","refresh_token":"new_refresh"}}"#,
+    )
+    .await;
+    let www = MockServer::start(
+        &[],
+        r#"<html><div id="1-name">refresh_csrf_value</div></html>"#,
+    )
+    .await;
+    let mut client = Client::builder()
+        .passport_host(&passport.url)
+        .www_host(&www.url)
+        .build()
+        .unwrap();
+    client.login_set_web_token(&WebToken {
+        dedeuserid: 1,
+        dedeuserid_ckmd5: "2".to_string(),
+        sessdata: "4".to_string(),
+        bili_jct: "old_jct".to_string(),
+        expires: 3,
+    });
+    client.login_set_refresh_token("old_refresh".to_string());
+    assert!(client.refresh_web_cookie_if_needed().await.unwrap());
+    assert_eq!(client.session().refresh_token(), Some("new_refresh"));
+    assert_eq!(client.csrf().as_deref(), Some("new_jct"));
+    let requests = passport.requests();
+    assert!(requests[0].starts_with("GET /x/passport-login/web/cookie/info?csrf=old_jct "));
+    assert!(requests[1].starts_with("POST /x/passport-login/web/cookie/refresh "));
+    assert!(requests[1].contains("refresh_csrf=refresh_csrf_value"));
+    assert!(requests[1].contains("refresh_token=old_refresh"));
+    assert!(requests[2].starts_with("POST /x/passport-login/web/confirm/refresh "));
+    assert!(requests[2].ends_with("csrf=new_jct&refresh_token=old_refresh"));
+    assert!(www.requests()[0].starts_with("GET /correspond/1/"));
+}
+
+#[tokio::test]
+async fn test_refresh_tv_token() {
+    let server = MockServer::start(
+        &[],
+        r#"{"code":0,"message":"0","ttl":1,"data":{"status":0,"token_info":{"mid":1,"access_token":"new_access","refresh_token":"new_refresh","expires_in":15552000},"cookie_info":{"cookies":[{"name":"SESSDATA","value":"web_sess","http_only":1,"expires":4111379116,"secure":0},{"name":"bili_jct","value":"web_jct","http_only":0,"expires":4111379116,"secure":0},{"name":"DedeUserID","value":"1","http_only":0,"expires":4111379116,"secure":0}],"domains":[".bilibili.com"]}}}"#,
+    )
+    .await;
+    let mut client = Client::builder()
+        .api_host(&server.url)
+        .passport_host(&server.url)
+        .build()
+        .unwrap();
+    client.login_set_tv_token(TvToken {
+        mid: 1,
+        access_token: "old_access".to_string(),
+        refresh_token: "old_refresh".to_string(),
+        expires_at: 0,
+    });
+    let web_token = client.exchange_web_cookie().await.unwrap();
+    assert_eq!(web_token.sessdata, "web_sess");
+    assert_eq!(web_token.bili_jct, "web_jct");
+    assert_eq!(web_token.expires, 4111379116);
+    let session = client.session();
+    let tv_token = session.tv_token().unwrap();
+    assert_eq!(tv_token.access_token, "new_access");
+    assert!(!tv_token.expired());
+    let requests = server.requests();
+    assert!(requests[0].starts_with("POST /x/passport-login/oauth2/refresh_token "));
+    assert!(requests[0].contains("access_key=old_access"));
+    assert!(requests[0].contains("refresh_token=old_refresh"));
+    assert!(requests[0].contains("sign="));
+
+    let _: serde_json::Value = client
+        .request_app_api(
+            reqwest::Method::GET,
+            "/x/v2/account/mine",
+            Some(serde_json::json!({"mid": 1})),
+        )
+        .await
+        .unwrap();
+    let request = &server.requests()[1];
+    assert!(request.starts_with("GET /x/v2/account/mine?"));
+    assert!(request.contains("access_key=new_access"));
+    assert!(request.contains("appkey=4409e2ce8ffd12b8"));
+    assert!(request.contains("sign="));
+}
+
+#[tokio::test]
+async fn test_exchange_web_cookie_without_cookie_info() {
+    let server = MockServer::start(
+        &[],
+        r#"{"code":0,"message":"0","ttl":1,"data":{"status":0,"token_info":{"mid":1,"access_token":"new_access","refresh_token":"new_refresh","expires_in":15552000}}}"#,
+    )
+    .await;
+    let mut client = Client::builder()
+        .passport_host(&server.url)
+        .build()
+        .unwrap();
+    client.login_set_web_token(&WebToken {
+        dedeuserid: 1,
+        dedeuserid_ckmd5: "2".to_string(),
+        sessdata: "stale".to_string(),
+        bili_jct: "5".to_string(),
+        expires: 3,
+    });
+    client.login_set_tv_token(TvToken {
+        mid: 1,
+        access_token: "old_access".to_string(),
+        refresh_token: "old_refresh".to_string(),
+        expires_at: 0,
+    });
+    // 没有返回cookie_info时不能把会话中原有的Cookie当作换取的结果
+    assert!(matches!(
+        client.exchange_web_cookie().await,
+        Err(Error::Format(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_login_qr_generate() {
+    let client = Client::new();
+    match client.login_qr_generate().await {
+        Ok(info) => {
+            println!("{}", serde_json::to_string(&info).unwrap())
+        }
+        Err(err) => {
+            println!("{}", err)
+        }
+    };
+}
+
+#[tokio::test]
+async fn test_login_qr_poll() {
+    let server = MockServer::start(
+        &[
+            ("Set-Cookie", "SESSDATA=sess%2Cdata; Path=/; Domain=bilibili.com; HttpOnly"),
+            ("Set-Cookie", "bili_jct=jct; Path=/; Domain=bilibili.com"),
+            ("Set-Cookie", "DedeUserID=1; Path=/; Domain=bilibili.com"),
+            ("Set-Cookie", "DedeUserID__ckMd5=md5; Path=/; Domain=bilibili.com"),
+        ],
+        r#"{"code":0,"message":"0","ttl":1,"data":{"url":"https://passport.biligame.com/x/passport-login/web/crossDomain?DedeUserID=1&DedeUserID__ckMd5=md5&Expires=4111379116&SESSDATA=sess%2Cdata&bili_jct=jct&gourl=https%3A%2F%2Fwww.bilibili.com","refresh_token":"refresh","timestamp":1684466082517,"code":0,"message":""}}"#,
+    )
+    .await;
+    let client = Client::builder()
+        .passport_host(&server.url)
+        .build()
+        .unwrap();
+    let poll = client.login_qr_poll("key".to_string()).await.unwrap();
+    assert_eq!(poll.status(), WebQrStatus::Confirmed);
+    let token = poll.token.unwrap();
+    assert_eq!(token.sessdata, "sess%2Cdata");
+    assert_eq!(token.bili_jct, "jct");
+    assert_eq!(token.dedeuserid, 1);
+    assert_eq!(token.expires, 4111379116);
+    assert_eq!(client.session().refresh_token(), Some("refresh"));
+    assert!(
+        server.requests()[0].starts_with("GET /x/passport-login/web/qrcode/poll?qrcode_key=key ")
+    );
+    assert_eq!(WebQrStatus::from_code(86090), WebQrStatus::Scanned);
+    assert_eq!(WebQrStatus::from_code(1).code(), 1);
+}
+
+#[tokio::test]
+async fn test_login_sms() {
+    // 同一个响应同时满足申请验证与发送短信的结构
+    let server = MockServer::start(
+        &[],
+        r#"{"code":0,"message":"0","ttl":1,"data":{"type":"geetest","token":"captcha_token","geetest":{"gt":"gt","challenge":"challenge"},"captcha_key":"captcha_key"}}"#,
+    )
+    .await;
+    let client = Client::builder()
+        .passport_host(&server.url)
+        .build()
+        .unwrap();
+    let solver = |geetest: Geetest| async move {
+        Ok(GeetestResult {
+            challenge: geetest.challenge,
+            validate: format!("validate_{}", geetest.gt),
+            seccode: "seccode|jordan".to_string(),
+        })
+    };
+    let send = client
+        .login_sms_send(86, "13800000000".to_string(), &solver)
+        .await
+        .unwrap();
+    assert_eq!(send.captcha_key, "captcha_key");
+    let requests = server.requests();
+    assert!(requests[0].starts_with("GET /x/passport-login/captcha?source=main_web "));
+    assert!(requests[1].starts_with("POST /x/passport-login/web/sms/send "));
+    assert!(requests[1].contains("token=captcha_token"));
+    assert!(requests[1].contains("challenge=challenge"));
+    assert!(requests[1].contains("validate=validate_gt"));
+    assert!(requests[1].contains("tel=13800000000"));
+
+    let server = MockServer::start(
+        &[("Set-Cookie", "SESSDATA=sess%2Cdata; Path=/; Domain=bilibili.com; HttpOnly")],
+        r#"{"code":0,"message":"0","ttl":1,"data":{"is_new":false,"status":0,"message":"","url":"https://passport.biligame.com/x/passport-login/web/crossDomain?DedeUserID=1&DedeUserID__ckMd5=md5&Expires=4111379116&SESSDATA=sess%2Cdata&bili_jct=jct&gourl=https%3A%2F%2Fwww.bilibili.com","refresh_token":"refresh","timestamp":1684466082517}}"#,
+    )
+    .await;
+    let client = Client::builder()
+        .passport_host(&server.url)
+        .build()
+        .unwrap();
+    let result = client
+        .login_sms(
+            86,
+            "13800000000".to_string(),
+            "123456".to_string(),
+            "captcha_key".to_string(),
+        )
+        .await
+        .unwrap();
+    let token = result.token.unwrap();
+    assert_eq!(token.dedeuserid, 1);
+    assert_eq!(token.bili_jct, "jct");
+    assert_eq!(client.session().refresh_token(), Some("refresh"));
+    assert!(server.requests()[0].contains("captcha_key=captcha_key"));
+}
+
+#[test]
+fn test_encrypt_password() {
+    use rsa::pkcs8::EncodePublicKey;
+    let private_key = rsa::RsaPrivateKey::new(&mut rand::thread_rng(), 1024).unwrap();
+    let key = LoginKey {
+        hash: "salt".to_string(),
+        key: rsa::RsaPublicKey::from(&private_key)
+            .to_public_key_pem(rsa::pkcs8::LineEnding::LF)
+            .unwrap(),
+    };
+    let encrypted = encrypt_password(&key, "password").unwrap();
+    let encrypted =
+        base64::Engine::decode(&base64::engine::general_purpose::STANDARD, encrypted).unwrap();
+    let decrypted = private_key
+        .decrypt(rsa::Pkcs1v15Encrypt, &encrypted)
+        .unwrap();
+    assert_eq!(decrypted, b"saltpassword");
+}
+
+#[tokio::test]
+async fn test_logout() {
+    let server = MockServer::start(
+        &[(
+            "Set-Cookie",
+            "SESSDATA=; Path=/; Domain=bilibili.com; Expires=Thu, 01 Jan 1970 00:00:00 GMT",
+        )],
+        r#"{"code":0,"status":true,"ts":1684466082,"data":{"redirectUrl":"https://www.bilibili.com"}}"#,
+    )
+    .await;
+    let mut client = Client::builder()
+        .passport_host(&server.url)
+        .build()
+        .unwrap();
+    let mut session = Session::new();
+    session.set("SESSDATA", "sess");
+    session.set("bili_jct", "jct");
+    session.set("DedeUserID", "1");
+    session.set("buvid3", "buvid");
+    session.set_refresh_token("refresh");
+    client.set_session(session);
+    client.logout().await.unwrap();
+    let session = client.session();
+    assert_eq!(session.web_token(), None);
+    assert_eq!(session.csrf(), None);
+    assert_eq!(session.mid(), None);
+    assert_eq!(session.refresh_token(), None);
+    assert_eq!(session.buvid3(), Some("buvid"));
+    let requests = server.requests();
+    assert!(requests[0].starts_with("POST /login/exit/v2 "));
+    assert!(requests[0].ends_with("biliCSRF=jct"));
+    assert!(matches!(
+        client.logout().await,
+        Err(Error::InvalidArgument(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_nav() {
+    let server = MockServer::start(
+        &[],
+        r#"{"code":-101,"message":"账号未登录","ttl":1,"data":{"isLogin":false,"wbi_img":{"img_url":"https://i0.hdslb.com/bfs/wbi/7cd084941338484aae1ad9425b84077c.png","sub_url":"https://i0.hdslb.com/bfs/wbi/4932caff0ff746eab6f01bf08b70ac45.png"}}}"#,
+    )
+    .await;
+    let client = Client::builder().api_host(&server.url).build().unwrap();
+    let nav = client.nav().await.unwrap();
+    assert!(!nav.is_login);
+    assert!(!nav.is_vip());
+    assert_eq!(nav.wallet.bcoin_balance, 0.0);
+    // 密钥已经缓存
+    client.wbi_keys().await.unwrap();
+    assert_eq!(server.requests().len(), 1);
+
+    let nav: crate::Nav = crate::from_str(
+        r#"{"isLogin":true,"mid":1,"uname":"bishi","money":12.5,"vipType":2,"vipStatus":1,"vipDueDate":4111379116000,"wallet":{"mid":1,"bcoin_balance":5,"coupon_balance":5,"coupon_due_time":0},"wbi_img":{"img_url":"","sub_url":""}}"#,
+    )
+    .unwrap();
+    assert!(nav.is_login && nav.is_vip());
+    assert_eq!(nav.money, 12.5);
+    assert_eq!(nav.wallet.bcoin_balance, 5.0);
+}
+
+#[tokio::test]
+async fn test_qr_login_poller() {
+    let poller = QrLoginPoller::new().interval(std::time::Duration::from_millis(1));
+    let mut script = vec![
+        QrLoginState::Waiting,
+        QrLoginState::Waiting,
+        QrLoginState::Scanned,
+        QrLoginState::Scanned,
+        QrLoginState::Confirmed(1),
+        QrLoginState::Waiting,
+    ]
+    .into_iter();
+    let states: Vec<QrLoginState<i64>> = poller
+        .stream(move || {
+            let state = script.next().unwrap();
+            async move { Ok(state) }
+        })
+        .map(|state| state.unwrap())
+        .collect()
+        .await;
+    assert_eq!(
+        states,
+        vec![
+            QrLoginState::Waiting,
+            QrLoginState::Scanned,
+            QrLoginState::Confirmed(1)
+        ]
+    );
+
+    let poller = poller.timeout(Some(std::time::Duration::from_millis(20)));
+    let states: Vec<crate::Result<QrLoginState<i64>>> = poller
+        .stream(|| async { Ok(QrLoginState::Waiting) })
+        .collect()
+        .await;
+    assert_eq!(states.len(), 2);
+    assert!(matches!(states[0], Ok(QrLoginState::Waiting)));
+    assert!(matches!(states[1], Err(Error::Timeout)));
+}
+
+#[tokio::test]
+async fn test_login_qr_stream() {
+    let server = MockServer::start(
+        &[],
+        r#"{"code":0,"message":"0","ttl":1,"data":{"url":"","refresh_token":"","timestamp":0,"code":86038,"message":"二维码已失效"}}"#,
+    )
+    .await;
+    let client = Client::builder()
+        .passport_host(&server.url)
+        .build()
+        .unwrap();
+    let states: Vec<crate::Result<QrLoginState<WebToken>>> = client
+        .login_qr_stream("key".to_string(), &QrLoginPoller::new())
+        .collect()
+        .await;
+    assert_eq!(states.len(), 1);
+    assert!(matches!(states[0], Ok(QrLoginState::Expired)));
+}
+
+#[cfg(feature = "qr")]
+#[test]
+fn test_qr_render() {
+    let data = crate::WebQrData {
+        url: "https://account.bilibili.com/h5/account-h5/auth/scan-web?qrcode_key=test".to_string(),
+        qrcode_key: "test".to_string(),
+    };
+    let unicode = data.qr_unicode().unwrap();
+    assert!(unicode.lines().count() > 10);
+    let ansi = data.qr_ansi().unwrap();
+    assert!(ansi.contains("\x1b[30;40m▀") && ansi.ends_with("\x1b[0m\n"));
+    assert!(data.qr_svg().unwrap().starts_with("<?xml"));
+    let png = data.qr_png(4).unwrap();
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    assert!(matches!(data.qr_png(0), Err(Error::InvalidArgument(_))));
+}
+
+#[test]
+fn test_dash_select() {
+    fn video(id: i64, codec: crate::VideoCodec, height: i64, bandwidth: i64) -> crate::Video {
+        let codecid = codec.code();
+        crate::Video {
+            id,
+            codecid,
+            height,
+            bandwidth,
+            base_url: format!("https://upos/{}-{}", id, codecid),
+            backup_url: vec![format!("https://backup/{}-{}", id, codecid)],
+            backup_url2: vec![format!("https://backup/{}-{}", id, codecid)],
+            ..Default::default()
+        }
+    }
+    fn audio(id: i64, bandwidth: i64) -> crate::Audio {
+        crate::Audio {
+            id,
+            bandwidth,
+            base_url: format!("https://upos/{}", id),
+            ..Default::default()
+        }
+    }
+    let dash = crate::Dash {
+        video: vec![
+            video(120, crate::VideoCodec::Avc, 2160, 12_000_000),
+            video(80, crate::VideoCodec::Avc, 1080, 3_000_000),
+            video(80, crate::VideoCodec::Hevc, 1080, 1_500_000),
+            video(80, crate::VideoCodec::Av1, 1080, 1_000_000),
+            video(64, crate::VideoCodec::Avc, 720, 1_200_000),
+            video(32, crate::VideoCodec::Avc, 480, 600_000),
+        ],
+        audio: vec![
+            audio(30216, 64_000),
+            audio(30280, 192_000),
+            audio(30250, 448_000),
+            audio(30251, 1_500_000),
+        ],
+        ..Default::default()
+    };
+
+    let selection = dash.select(&crate::StreamPreference::new());
+    assert_eq!(selection.video.as_ref().unwrap().id, 120);
+    assert_eq!(selection.audio.as_ref().unwrap().id, 30280);
+    assert_eq!(
+        selection.video_urls,
+        vec!["https://upos/120-7", "https://backup/120-7"]
+    );
+    assert_eq!(selection.audio_urls, vec!["https://upos/30280"]);
+
+    let preference = crate::StreamPreference::new()
+        .max_quality(crate::VIDEO_QUALITY_1080P)
+        .codecs(vec![crate::VideoCodec::Hevc, crate::VideoCodec::Avc])
+        .prefer_dolby(true);
+    let selection = dash.select(&preference);
+    let video = selection.video.unwrap();
+    assert_eq!(
+        (video.id, video.codec()),
+        (80, Some(crate::VideoCodec::Hevc))
+    );
+    assert_eq!(selection.audio.unwrap().id, 30250);
+
+    let preference = crate::StreamPreference::new()
+        .max_height(1080)
+        .max_bandwidth(1_100_000)
+        .prefer_dolby(true)
+        .prefer_hi_res(true);
+    let selection = dash.select(&preference);
+    let video = selection.video.unwrap();
+    assert_eq!(
+        (video.id, video.codec()),
+        (80, Some(crate::VideoCodec::Av1))
+    );
+    assert_eq!(selection.audio.unwrap().id, 30251);
+
+    // 没有满足限制的流时选择最低画质
+    let selection = dash.select(&crate::StreamPreference::new().max_bandwidth(1));
+    assert_eq!(selection.video.unwrap().id, 32);
+    assert_eq!(
+        crate::Dash::default().select(&crate::StreamPreference::new()),
+        crate::DashSelection::default()
+    );
+}
+
+/// 支持Range请求的模拟CDN
+async fn range_server(data: Vec<u8>) -> MockServer {
+    MockServer::start_with(move |request| {
+        let range = request
+            .to_lowercase()
+            .lines()
+            .find_map(|line| line.strip_prefix("range: bytes=").map(str::to_string));
+        match range {
+            Some(range) => {
+                let (start, end) = range.trim().split_once('-').unwrap();
+                let (start, end): (usize, usize) = (start.parse().unwrap(), end.parse().unwrap());
+                let end = end.min(data.len() - 1);
+                let mut response = format!(
+                    "HTTP/1.1 206 Partial Content\r\nContent-Length: {}\r\nContent-Range: bytes {}-{}/{}\r\nConnection: close\r\n\r\n",
+                    end - start + 1,
+                    start,
+                    end,
+                    data.len()
+                )
+                .into_bytes();
+                response.extend_from_slice(&data[start..=end]);
+                response
+            }
+            None => {
+                let mut response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    data.len()
+                )
+                .into_bytes();
+                response.extend_from_slice(&data);
+                response
+            }
+        }
+    })
+    .await
+}
+
+#[tokio::test]
+async fn test_download() {
+    let data: Vec<u8> = (0..100u8).collect();
+    let server = range_server(data.clone()).await;
+    let dead = MockServer::start_with(|_| {
+        b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_vec()
+    })
+    .await;
+    let dir = std::env::temp_dir().join(format!("bilirust-download-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let client = Client::new();
+
+    // 主地址失败时使用备用地址
+    let path = dir.join("video.m4s");
+    let progress = Arc::new(Mutex::new(vec![]));
+    let progress_clone = progress.clone();
+    let downloader = client
+        .downloader()
+        .chunk_size(16)
+        .concurrency(3)
+        .retries(2)
+        .progress(move |p| progress_clone.lock().unwrap().push(p));
+    let video = crate::Video {
+        base_url: format!("{}/video.m4s", dead.url),
+        backup_url: vec![format!("{}/video.m4s", server.url)],
+        ..Default::default()
+    };
+    assert_eq!(downloader.download(&video, &path).await.unwrap(), 100);
+    assert_eq!(std::fs::read(&path).unwrap(), data);
+    let last = *progress.lock().unwrap().last().unwrap();
+    assert_eq!((last.downloaded, last.total), (100, 100));
+    // 探测 + 7块
+    let requests = server.requests();
+    assert_eq!(requests.len(), 8);
+    assert!(requests
+        .iter()
+        .all(|r| r.contains("referer: https://www.bilibili.com")));
+    assert!(!dir.join("video.m4s.part").exists());
+    assert!(!dir.join("video.m4s.part.json").exists());
+
+    // 从已完成的块继续
+    let server = range_server(data.clone()).await;
+    let path = dir.join("resume.m4s");
+    let mut part = data.clone();
+    part[32..].fill(0);
+    std::fs::write(dir.join("resume.m4s.part"), &part).unwrap();
+    std::fs::write(
+        dir.join("resume.m4s.part.json"),
+        r#"{"total":100,"chunk_size":16,"done":[true,true,false,false,false,false,false]}"#,
+    )
+    .unwrap();
+    let urls = vec![format!("{}/resume.m4s", server.url)];
+    let downloader = client.downloader().chunk_size(16);
+    downloader.download(&urls, &path).await.unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), data);
+    assert_eq!(server.requests().len(), 6);
+
+    // 不支持Range时整体下载
+    let server = MockServer::start(&[], "flv data").await;
+    let path = dir.join("video.flv");
+    let durl = crate::Durl {
+        url: format!("{}/video.flv", server.url),
+        ..Default::default()
+    };
+    assert_eq!(downloader.download(&durl, &path).await.unwrap(), 8);
+    assert_eq!(std::fs::read(&path).unwrap(), b"flv data");
+
+    // 总大小未知时同样整体下载
+    let server = MockServer::start_with(|request| {
+        if request.to_lowercase().contains("\r\nrange: ") {
+            b"HTTP/1.1 206 Partial Content\r\nContent-Length: 1\r\nContent-Range: bytes 0-0/*\r\nConnection: close\r\n\r\nl".to_vec()
+        } else {
+            b"HTTP/1.1 200 OK\r\nContent-Length: 9\r\nConnection: close\r\n\r\nlive data".to_vec()
+        }
+    })
+    .await;
+    let path = dir.join("live.flv");
+    let urls = vec![format!("{}/live.flv", server.url)];
+    assert_eq!(downloader.download(&urls, &path).await.unwrap(), 9);
+    assert_eq!(std::fs::read(&path).unwrap(), b"live data");
+    assert_eq!(server.requests().len(), 2);
+
+    assert!(matches!(
+        downloader.download(&Vec::<String>::new(), &path).await,
+        Err(Error::InvalidArgument(_))
+    ));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "mux")]
+fn mp4_box(typ: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    [&(payload.len() as u32 + 8).to_be_bytes()[..], typ, payload].concat()
+}
+
+/// 构造单轨道的fMP4, fragments中的样本为 (时长, 数据, 是否为关键帧)
+#[cfg(feature = "mux")]
+fn fmp4(handler: &[u8; 4], timescale: u32, fragments: &[Vec<(u32, Vec<u8>, bool)>]) -> Vec<u8> {
+    let full = |version: u8, flags: u32, rest: &[u8]| {
+        let mut data = flags.to_be_bytes().to_vec();
+        data[0] = version;
+        data.extend_from_slice(rest);
+        data
+    };
+    let mvhd = full(
+        0,
+        0,
+        &[
+            &[0u8; 8][..],
+            &1000u32.to_be_bytes(),
+            &[0u8; 80],
+            &2u32.to_be_bytes(),
+        ]
+        .concat(),
+    );
+    let tkhd = full(
+        0,
+        3,
+        &[&[0u8; 8][..], &1u32.to_be_bytes(), &[0u8; 68]].concat(),
+    );
+    let mdhd = full(
+        0,
+        0,
+        &[&[0u8; 8][..], &timescale.to_be_bytes(), &[0u8; 8]].concat(),
+    );
+    let hdlr = full(0, 0, &[&[0u8; 4][..], handler, &[0u8; 13]].concat());
+    let empty = full(0, 0, &0u32.to_be_bytes());
+    let stbl = [
+        mp4_box(b"stsd", &empty),
+        mp4_box(b"stts", &empty),
+        mp4_box(b"stsc", &empty),
+        mp4_box(b"stsz", &full(0, 0, &[0u8; 8])),
+        mp4_box(b"stco", &empty),
+    ]
+    .concat();
+    let minf = mp4_box(b"minf", &mp4_box(b"stbl", &stbl));
+    let mdia = mp4_box(
+        b"mdia",
+        &[mp4_box(b"mdhd", &mdhd), mp4_box(b"hdlr", &hdlr), minf].concat(),
+    );
+    // 编辑列表只描述了第一个分片的时长
+    let elst = full(
+        0,
+        0,
+        &[&1u32.to_be_bytes()[..], &40u32.to_be_bytes(), &[0u8; 8]].concat(),
+    );
+    let edts = mp4_box(b"edts", &mp4_box(b"elst", &elst));
+    let trak = mp4_box(b"trak", &[mp4_box(b"tkhd", &tkhd), edts, mdia].concat());
+    let trex = full(
+        0,
+        0,
+        &[&1u32.to_be_bytes()[..], &1u32.to_be_bytes(), &[0u8; 12]].concat(),
+    );
+    let mvex = mp4_box(b"mvex", &mp4_box(b"trex", &trex));
+    let mut data = [
+        mp4_box(b"ftyp", b"iso5\0\0\0\0iso6mp41"),
+        mp4_box(b"moov", &[mp4_box(b"mvhd", &mvhd), trak, mvex].concat()),
+    ]
+    .concat();
+    for (sequence, samples) in fragments.iter().enumerate() {
+        let moof = |data_offset: u32| {
+            let mut trun = (samples.len() as u32).to_be_bytes().to_vec();
+            trun.extend(data_offset.to_be_bytes());
+            for (duration, sample, sync) in samples {
+                trun.extend(duration.to_be_bytes());
+                trun.extend((sample.len() as u32).to_be_bytes());
+                trun.extend((if *sync { 0u32 } else { 0x0001_0000 }).to_be_bytes());
+            }
+            let traf = [
+                mp4_box(b"tfhd", &full(0, 0x02_0000, &1u32.to_be_bytes())),
+                mp4_box(b"tfdt", &full(1, 0, &[0u8; 8])),
+                mp4_box(b"trun", &full(0, 0x0701, &trun)),
+            ]
+            .concat();
+            mp4_box(
+                b"moof",
+                &[
+                    mp4_box(b"mfhd", &full(0, 0, &(sequence as u32 + 1).to_be_bytes())),
+                    mp4_box(b"traf", &traf),
+                ]
+                .concat(),
+            )
+        };
+        let moof_len = moof(0).len() as u32;
+        data.extend(moof(moof_len + 8));
+        let mdat: Vec<u8> = samples
+            .iter()
+            .flat_map(|(_, sample, _)| sample.clone())
+            .collect();
+        data.extend(mp4_box(b"mdat", &mdat));
+    }
+    data
+}
+
+/// 按路径查找box的内容
+#[cfg(feature = "mux")]
+fn mp4_find<'a>(data: &'a [u8], path: &[&[u8; 4]]) -> Vec<&'a [u8]> {
+    let mut found = vec![];
+    let mut offset = 0;
+    while offset + 8 <= data.len() {
+        let size = u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap()) as usize;
+        let (header, size) = if size == 1 {
+            (
+                16,
+                u64::from_be_bytes(data[offset + 8..offset + 16].try_into().unwrap()) as usize,
+            )
+        } else {
+            (8, size)
+        };
+        if &data[offset + 4..offset + 8] == path[0] {
+            let payload = &data[offset + header..offset + size];
+            if path.len() == 1 {
+                found.push(payload);
+            } else {
+                found.extend(mp4_find(payload, &path[1..]));
+            }
+        }
+        offset += size;
+    }
+    found
+}
+
+#[cfg(feature = "mux")]
+#[test]
+fn test_remux_dash() {
+    let u32_at = |data: &[u8], offset: usize| {
+        u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap())
+    };
+    let video = fmp4(
+        b"vide",
+        16000,
+        &[
+            vec![
+                (512, b"video-1".to_vec(), true),
+                (512, b"video-22".to_vec(), false),
+            ],
+            vec![(512, b"video-333".to_vec(), true)],
+        ],
+    );
+    let audio = fmp4(
+        b"soun",
+        48000,
+        &[
+            vec![(1024, b"audio-1".to_vec(), true)],
+            vec![(1024, b"audio-22".to_vec(), true)],
+        ],
+    );
+    let dir = std::env::temp_dir().join(format!("bilirust-mux-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("video.m4s"), &video).unwrap();
+    std::fs::write(dir.join("audio.m4s"), &audio).unwrap();
+    crate::remux_dash(
+        dir.join("video.m4s"),
+        dir.join("audio.m4s"),
+        dir.join("out.mp4"),
+    )
+    .unwrap();
+    let out = std::fs::read(dir.join("out.mp4")).unwrap();
+
+    assert_eq!(&out[4..8], b"ftyp");
+    assert_eq!(mp4_find(&out, &[b"mdat"]).len(), 1);
+    assert!(mp4_find(&out, &[b"moov", b"mvex"]).is_empty());
+    // mvhd的duration为最长的轨道 (1000: 1536/16000 = 96ms)
+    let mvhd = mp4_find(&out, &[b"moov", b"mvhd"])[0];
+    assert_eq!(u32_at(mvhd, 16), 96);
+    assert_eq!(u32_at(mvhd, mvhd.len() - 4), 3);
+
+    let traks = mp4_find(&out, &[b"moov", b"trak"]);
+    assert_eq!(traks.len(), 2);
+    let expected: [&[&[u8]]; 2] = [
+        &[b"video-1", b"video-22", b"video-333"],
+        &[b"audio-1", b"audio-22"],
+    ];
+    for (index, trak) in traks.iter().enumerate() {
+        let tkhd = mp4_find(trak, &[b"tkhd"])[0];
+        assert_eq!(u32_at(tkhd, 12), index as u32 + 1);
+        assert!(mp4_find(trak, &[b"edts"]).is_empty());
+        let stbl = mp4_find(trak, &[b"mdia", b"minf", b"stbl"])[0];
+        let stsz = mp4_find(stbl, &[b"stsz"])[0];
+        let stco = mp4_find(stbl, &[b"stco"])[0];
+        let stsc = mp4_find(stbl, &[b"stsc"])[0];
+        let sizes: Vec<usize> = (0..u32_at(stsz, 8) as usize)
+            .map(|i| u32_at(stsz, 12 + i * 4) as usize)
+            .collect();
+        let chunks: Vec<usize> = (0..u32_at(stco, 4) as usize)
+            .map(|i| u32_at(stco, 8 + i * 4) as usize)
+            .collect();
+        // 解析stsc得到每个chunk的样本数
+        let entries: Vec<(usize, usize)> = (0..u32_at(stsc, 4) as usize)
+            .map(|i| {
+                (
+                    u32_at(stsc, 8 + i * 12) as usize,
+                    u32_at(stsc, 12 + i * 12) as usize,
+                )
+            })
+            .collect();
+        let mut samples = vec![];
+        let mut sample = 0;
+        for (chunk, offset) in chunks.iter().enumerate() {
+            let per_chunk = entries
+                .iter()
+                .rev()
+                .find(|(first, _)| *first <= chunk + 1)
+                .unwrap()
+                .1;
+            let mut offset = *offset;
+            for _ in 0..per_chunk {
+                samples.push(out[offset..offset + sizes[sample]].to_vec());
+                offset += sizes[sample];
+                sample += 1;
+            }
+        }
+        assert_eq!(samples, expected[index]);
+    }
+    let video_stbl = mp4_find(traks[0], &[b"mdia", b"minf", b"stbl"])[0];
+    let stss = mp4_find(video_stbl, &[b"stss"])[0];
+    assert_eq!(
+        (u32_at(stss, 4), u32_at(stss, 8), u32_at(stss, 12)),
+        (2, 1, 3)
+    );
+    let stts = mp4_find(video_stbl, &[b"stts"])[0];
+    assert_eq!(
+        (u32_at(stts, 4), u32_at(stts, 8), u32_at(stts, 12)),
+        (1, 3, 512)
+    );
+    assert!(mp4_find(traks[1], &[b"mdia", b"minf", b"stbl", b"stss"]).is_empty());
+
+    // 使用SegmentBase定位初始化段
+    let init_len = 8 + 16 + u32::from_be_bytes(video[24..28].try_into().unwrap()) as usize;
+    let segment_base = crate::SegmentBase {
+        initialization: format!("0-{}", init_len - 1),
+        // 没有sidx时紧接着初始化段扫描
+        index_range: String::new(),
+    };
+    assert_eq!(
+        segment_base.initialization_range(),
+        Some((0, init_len as u64 - 1))
+    );
+    crate::remux(
+        &[crate::MuxTrack::new(dir.join("video.m4s")).segment_base(segment_base)],
+        dir.join("video.mp4"),
+    )
+    .unwrap();
+    let traks_video_only = std::fs::read(dir.join("video.mp4")).unwrap();
+    assert_eq!(mp4_find(&traks_video_only, &[b"moov", b"trak"]).len(), 1);
+    assert!(matches!(
+        crate::remux(&[], dir.join("empty.mp4")),
+        Err(Error::InvalidArgument(_))
+    ));
+
+    // tkhd被截断时返回错误
+    let mut truncated = video.clone();
+    let box_at = |data: &[u8], typ: &[u8; 4]| data.windows(4).position(|w| w == typ).unwrap() - 4;
+    let tkhd = box_at(&truncated, b"tkhd");
+    let cut = u32_at(&truncated, tkhd) - 12;
+    truncated.drain(tkhd + 12..tkhd + 12 + cut as usize);
+    for offset in [
+        box_at(&truncated, b"moov"),
+        box_at(&truncated, b"trak"),
+        tkhd,
+    ] {
+        let size = u32_at(&truncated, offset) - cut;
+        truncated[offset..offset + 4].copy_from_slice(&size.to_be_bytes());
+    }
+    std::fs::write(dir.join("truncated.m4s"), &truncated).unwrap();
+    assert!(matches!(
+        crate::remux(
+            &[crate::MuxTrack::new(dir.join("truncated.m4s"))],
+            dir.join("truncated.mp4")
+        ),
+        Err(Error::Format(_))
+    ));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// 构造FLV, tags为 (类型, 时间戳, 数据)
+fn flv(tags: &[(u8, u32, Vec<u8>)]) -> Vec<u8> {
+    let mut data = b"FLV\x01\x05\x00\x00\x00\x09\x00\x00\x00\x00".to_vec();
+    for (tag_type, timestamp, body) in tags {
+        let size = (body.len() as u32).to_be_bytes();
+        let ts = timestamp.to_be_bytes();
+        data.extend([
+            *tag_type, size[1], size[2], size[3], ts[1], ts[2], ts[3], ts[0], 0, 0, 0,
+        ]);
+        data.extend(body);
+        data.extend((body.len() as u32 + 11).to_be_bytes());
+    }
+    data
+}
+
+#[test]
+fn test_concat_flv() {
+    // onMetaData: {width: 1920, duration: 0.1, keyframes: {times: [0]}}
+    let mut meta = b"\x02\x00\x0aonMetaData\x08\x00\x00\x00\x03".to_vec();
+    meta.extend(b"\x00\x05width\x00");
+    meta.extend(1920f64.to_be_bytes());
+    meta.extend(b"\x00\x09keyframes\x03\x00\x05times\x0a\x00\x00\x00\x01\x00");
+    meta.extend(0f64.to_be_bytes());
+    meta.extend(b"\x00\x00\x09");
+    meta.extend(b"\x00\x08duration\x00");
+    meta.extend(0.1f64.to_be_bytes());
+    meta.extend(b"\x00\x00\x09");
+    let segment = |frame: u8| {
+        flv(&[
+            (18, 0, meta.clone()),
+            (9, 0, vec![0x17, 0, 0, 0, 0, 1]),
+            (8, 0, vec![0xAF, 0, 0x12]),
+            (9, 0, vec![0x17, 1, 0, 0, 0, frame]),
+            (8, 0, vec![0xAF, 1, frame]),
+            (9, 40, vec![0x27, 1, 0, 0, 0, frame]),
+            (8, 23, vec![0xAF, 1, frame]),
+            (9, 80, vec![0x27, 1, 0, 0, 0, frame]),
+        ])
+    };
+    let dir = std::env::temp_dir().join(format!("bilirust-flv-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("1.flv"), segment(1)).unwrap();
+    std::fs::write(dir.join("2.flv"), segment(2)).unwrap();
+    let duration =
+        crate::concat_flv(&[dir.join("1.flv"), dir.join("2.flv")], dir.join("out.flv")).unwrap();
+    // 每段80ms + 40ms的帧间隔
+    assert_eq!(duration, 240);
+    let out = std::fs::read(dir.join("out.flv")).unwrap();
+
+    let mut tags = vec![];
+    let mut pos = 13;
+    while pos < out.len() {
+        let size = u32::from_be_bytes([0, out[pos + 1], out[pos + 2], out[pos + 3]]) as usize;
+        let ts = u32::from_be_bytes([out[pos + 7], out[pos + 4], out[pos + 5], out[pos + 6]]);
+        tags.push((out[pos], ts, out[pos + 11..pos + 11 + size].to_vec()));
+        let previous =
+            u32::from_be_bytes(out[pos + 11 + size..pos + 15 + size].try_into().unwrap());
+        assert_eq!(previous as usize, size + 11);
+        pos += size + 15;
+    }
+    // 第二段去掉onMetaData与两个序列头
+    assert_eq!(tags.len(), 8 + 5);
+    assert_eq!(tags.iter().filter(|t| t.0 == 18).count(), 1);
+    let timestamps: Vec<u32> = tags.iter().skip(3).map(|t| t.1).collect();
+    assert_eq!(timestamps, vec![0, 0, 40, 23, 80, 120, 120, 160, 143, 200]);
+    // duration被修正为0.24秒, 其他属性不变
+    let meta = &tags[0].2;
+    assert!(meta.ends_with(&[&0.24f64.to_be_bytes()[..], b"\x00\x00\x09"].concat()));
+    assert!(meta.windows(8).any(|w| w == 1920f64.to_be_bytes()));
+
+    let invalid = dir.join("invalid.flv");
+    std::fs::write(&invalid, b"not a flv file").unwrap();
+    assert!(matches!(
+        crate::concat_flv(&[&invalid], dir.join("out2.flv")),
+        Err(Error::Format(_))
+    ));
+
+    // 第二段的时间戳接上第一段后超出u32
+    let near_max = flv(&[
+        (9, 0, vec![0x17, 1, 0, 0, 0, 1]),
+        (9, u32::MAX - 10, vec![0x27, 1, 0, 0, 0, 1]),
+    ]);
+    std::fs::write(dir.join("max.flv"), near_max).unwrap();
+    assert!(matches!(
+        crate::concat_flv(
+            &[dir.join("max.flv"), dir.join("max.flv")],
+            dir.join("out3.flv")
+        ),
+        Err(Error::Format(_))
+    ));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_download_flv() {
+    let segment = flv(&[
+        (9, 0, vec![0x17, 1, 0, 0, 0, 1]),
+        (9, 40, vec![0x27, 1, 0, 0, 0, 2]),
+    ]);
+    let server = range_server(segment).await;
+    let dir = std::env::temp_dir().join(format!("bilirust-download-flv-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let durls: Vec<crate::Durl> = [2, 1]
+        .into_iter()
+        .map(|order| crate::Durl {
+            order,
+            url: format!("{}/{}.flv", server.url, order),
+            ..Default::default()
+        })
+        .collect();
+    let duration = Client::new()
+        .downloader()
+        .download_flv(&durls, dir.join("video.flv"))
+        .await
+        .unwrap();
+    // 每段40ms + 40ms的帧间隔
+    assert_eq!(duration, 160);
+    assert!(!dir.join("video.flv.1.flv").exists());
+    // 按order下载
+    let requests = server.requests();
+    assert!(requests[0].starts_with("GET /1.flv "));
+    assert!(requests.last().unwrap().starts_with("GET /2.flv "));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_dash_to_mpd() {
+    let segment_base = crate::SegmentBase {
+        initialization: "0-927".to_string(),
+        index_range: "928-1287".to_string(),
+    };
+    let video_url = crate::VideoUrl {
+        timelength: 123456,
+        dash: crate::Dash {
+            duration: 124,
+            min_buffer_time: 1.5,
+            video: vec![
+                crate::Video {
+                    id: 80,
+                    base_url: "https://upos/80.m4s?a=1&b=2".to_string(),
+                    backup_url: vec!["https://backup/80.m4s".to_string()],
+                    bandwidth: 2000000,
+                    mime_type: "video/mp4".to_string(),
+                    codecs: "avc1.640032".to_string(),
+                    width: 1920,
+                    height: 1080,
+                    frame_rate: "29.412".to_string(),
+                    sar: "1:1".to_string(),
+                    segment_base: segment_base.clone(),
+                    codecid: crate::VideoCodec::Avc.code(),
+                    ..Default::default()
+                },
+                crate::Video {
+                    id: 80,
+                    base_url: "https://upos/80-hevc.m4s".to_string(),
+                    codecs: "hev1.1.6.L120.90".to_string(),
+                    codecid: crate::VideoCodec::Hevc.code(),
+                    ..Default::default()
+                },
+            ],
+            audio: vec![crate::Audio {
+                id: 30280,
+                base_url: "https://upos/30280.m4s".to_string(),
+                bandwidth: 192000,
+                mime_type: "audio/mp4".to_string(),
+                codecs: "mp4a.40.2".to_string(),
+                segment_base,
+                ..Default::default()
+            }],
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mpd = video_url.to_mpd();
+    assert!(mpd.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<MPD "));
+    assert!(mpd.contains(
+        "type=\"static\" mediaPresentationDuration=\"PT123.456S\" minBufferTime=\"PT1.5S\""
+    ));
+    assert_eq!(mpd.matches("<AdaptationSet ").count(), 3);
+    assert_eq!(mpd.matches("</AdaptationSet>").count(), 3);
+    assert!(mpd.contains("<Representation id=\"80-7\" bandwidth=\"2000000\" codecs=\"avc1.640032\" width=\"1920\" height=\"1080\" frameRate=\"29.412\" sar=\"1:1\">"));
+    assert!(mpd.contains("<BaseURL>https://upos/80.m4s?a=1&amp;b=2</BaseURL>\n        <BaseURL>https://backup/80.m4s</BaseURL>"));
+    assert!(mpd.contains(
+        "<SegmentBase indexRange=\"928-1287\">\n          <Initialization range=\"0-927\"/>"
+    ));
+    assert!(mpd.contains("contentType=\"audio\" mimeType=\"audio/mp4\""));
+    assert!(mpd.contains("<Representation id=\"30280\" bandwidth=\"192000\" codecs=\"mp4a.40.2\">"));
+    assert!(mpd.ends_with("  </Period>\n</MPD>\n"));
+    assert!(video_url
+        .dash
+        .to_mpd()
+        .contains("mediaPresentationDuration=\"PT124S\""));
+}
+
+#[test]
+fn test_codec_and_quality() {
+    use crate::{AudioQuality, VideoCodec, VideoQuality};
+
+    assert_eq!(VideoCodec::try_from(12).unwrap(), VideoCodec::Hevc);
+    assert_eq!(VideoCodec::Av1.code(), 13);
+    assert_eq!(VideoCodec::Avc.to_string(), "AVC/H.264");
+    assert!(matches!(
+        VideoCodec::try_from(99),
+        Err(Error::InvalidArgument(_))
+    ));
+    assert!(AudioQuality::Q192K > AudioQuality::Q64K);
+    assert!(AudioQuality::HiRes > AudioQuality::Dolby);
+    assert_eq!(
+        serde_json::from_str::<AudioQuality>("30250").unwrap(),
+        AudioQuality::Dolby
+    );
+    assert_eq!(
+        serde_json::to_string(&AudioQuality::Q132K).unwrap(),
+        "30232"
+    );
+
+    let video = crate::Video {
+        id: 120,
+        codecid: 7,
+        ..Default::default()
+    };
+    assert_eq!(video.codec(), Some(VideoCodec::Avc));
+    assert_eq!(video.quality(), crate::VIDEO_QUALITY_4K);
+    let audio = crate::Audio {
+        id: 30251,
+        ..Default::default()
+    };
+    assert_eq!(audio.quality(), Some(AudioQuality::HiRes));
+
+    let quality = VideoQuality::from_code(120);
+    assert_eq!(quality.label(), "4K 超清");
+    assert!(quality.need_login() && quality.need_vip() && quality.need_fourk());
+    assert_eq!(quality.fnval(), FNVAL_DASH | crate::FNVAL_DASH_4K);
+    assert!(!VIDEO_QUALITY_720P.need_login());
+    assert!(crate::VIDEO_QUALITY_1080P.need_login() && !crate::VIDEO_QUALITY_1080P.need_vip());
+    assert_eq!(crate::VIDEO_QUALITY_240P.fnval(), crate::FNVAL_MP4);
+    assert!(crate::VIDEO_QUALITY_8K > crate::VIDEO_QUALITY_HDR);
+    assert_eq!(VideoQuality::from_code(1).to_string(), "1");
+}
+
+#[test]
+fn test_fnval() {
+    use crate::{Fnval, PlayUrlRequest, FNVAL_DASH_4K, FNVAL_DASH_AV1, FNVAL_DASH_HDR};
+
+    let fnval = Fnval::dash().with_4k().with_hdr().with_av1();
+    assert_eq!(
+        fnval.bits(),
+        FNVAL_DASH | FNVAL_DASH_4K | FNVAL_DASH_HDR | FNVAL_DASH_AV1
+    );
+    assert!(fnval.is_dash() && fnval.need_fourk());
+    assert_eq!(
+        Fnval::from(FNVAL_DASH) | FNVAL_DASH_HDR,
+        Fnval::dash().with_hdr()
+    );
+    assert!(fnval.validate(crate::VIDEO_QUALITY_4K).is_ok());
+    assert!(fnval.validate(crate::VIDEO_QUALITY_8K).is_err());
+    assert!(Fnval::mp4().with_4k().validate(VIDEO_QUALITY_720P).is_err());
+    assert!((Fnval::mp4() | Fnval::dash())
+        .validate(VIDEO_QUALITY_720P)
+        .is_err());
+    assert_eq!(
+        Fnval::for_quality(crate::VIDEO_QUALITY_HDR),
+        Fnval::dash().with_hdr()
+    );
+    assert_eq!(Fnval::for_quality(VIDEO_QUALITY_720P), Fnval::dash());
+
+    let request = PlayUrlRequest::new("BV1fK4y1t7hj", 196018899)
+        .quality(crate::VIDEO_QUALITY_4K)
+        .fnval(Fnval::dash());
+    assert!(matches!(request.validate(), Err(Error::InvalidArgument(_))));
+    let request = request.fnval(Fnval::dash().with_4k()).fourk(false);
+    assert!(request.validate().is_err());
+    let request = request.fourk(true).platform("html5").high_quality(true);
+    assert!(request.validate().is_ok());
+    assert_eq!(
+        request.query(),
+        serde_json::json!({
+            "bvid": "BV1fK4y1t7hj",
+            "cid": 196018899,
+            "qn": 120,
+            "fnval": 144,
+            "fnver": 0,
+            "fourk": 1,
+            "platform": "html5",
+            "high_quality": 1,
+        })
+    );
+}
+
+#[test]
+fn test_dash_dolby_flac() {
+    let dash: crate::Dash = serde_json::from_value(serde_json::json!({
+        "duration": 10,
+        "minBufferTime": 1.5,
+        "min_buffer_time": 1.5,
+        "video": [],
+        "audio": [{
+            "id": 30280,
+            "baseUrl": "",
+            "base_url": "https://upos/30280.m4s",
+            "backupUrl": null,
+            "backup_url": null,
+            "bandwidth": 192000,
+            "mimeType": "audio/mp4",
+            "mime_type": "audio/mp4",
+            "codecs": "mp4a.40.2",
+            "width": 0,
+            "height": 0,
+            "frameRate": "",
+            "frame_rate": "",
+            "sar": "",
+            "startWithSap": 0,
+            "start_with_sap": 0,
+            "segment_base": {"initialization": "0-817", "index_range": "818-1000"},
+            "codecid": 0
+        }],
+        "dolby": {
+            "type": 2,
+            "audio": [{
+                "id": 30250,
+                "base_url": "https://upos/30250.m4s",
+                "backup_url": ["https://backup/30250.m4s"],
+                "bandwidth": 448000,
+                "mime_type": "audio/mp4",
+                "codecs": "ec-3",
+                "segment_base": {"initialization": "0-1000", "index_range": "1001-2000"},
+                "size": 123
+            }]
+        },
+        "flac": {
+            "display": true,
+            "audio": {
+                "id": 30251,
+                "base_url": "https://upos/30251.m4s",
+                "backup_url": null,
+                "bandwidth": 1000000,
+                "mime_type": "audio/mp4",
+                "codecs": "fLaC",
+                "segment_base": {"initialization": "0-900", "index_range": "901-1500"}
+            }
+        }
+    }))
+    .unwrap();
+    assert_eq!(dash.dolby.as_ref().unwrap().dolby_type, 2);
+    assert_eq!(dash.dolby.as_ref().unwrap().audio[0].backup_url2.len(), 1);
+    assert!(dash.flac.as_ref().unwrap().display);
+    let ids: Vec<i64> = dash.all_audio().iter().map(|audio| audio.id).collect();
+    assert_eq!(ids, vec![30280, 30250, 30251]);
+
+    let selection = dash.select(&crate::StreamPreference::new());
+    assert_eq!(selection.audio.unwrap().id, 30280);
+    let selection = dash.select(&crate::StreamPreference::new().prefer_dolby(true));
+    assert_eq!(
+        selection.audio_urls,
+        vec!["https://upos/30250.m4s", "https://backup/30250.m4s"]
+    );
+    let selection = dash.select(&crate::StreamPreference::new().prefer_hi_res(true));
+    assert_eq!(
+        selection.audio.unwrap().quality(),
+        Some(crate::AudioQuality::HiRes)
+    );
+    let mpd = dash.to_mpd();
+    assert!(mpd.contains("<Representation id=\"30251\" bandwidth=\"1000000\" codecs=\"fLaC\">"));
+    // 普通音频, 杜比音频, Hi-Res无损音频各自一个AdaptationSet
+    assert_eq!(mpd.matches("contentType=\"audio\"").count(), 3);
+    assert!(mpd.contains(
+        "<AdaptationSet id=\"0\" contentType=\"audio\" mimeType=\"audio/mp4\" codecs=\"mp4a.40.2\""
+    ));
+    assert!(mpd.contains(
+        "<AdaptationSet id=\"1\" contentType=\"audio\" mimeType=\"audio/mp4\" codecs=\"ec-3\""
+    ));
+    assert!(mpd.contains(
+        "<AdaptationSet id=\"2\" contentType=\"audio\" mimeType=\"audio/mp4\" codecs=\"fLaC\""
+    ));
+
+    let dash: crate::Dash = serde_json::from_value(serde_json::json!({
+        "duration": 10,
+        "minBufferTime": 1.5,
+        "min_buffer_time": 1.5,
+        "video": [],
+        "audio": [],
+        "dolby": {"type": 0, "audio": null},
+        "flac": null
+    }))
+    .unwrap();
+    assert!(dash.dolby.unwrap().audio.is_empty());
+    assert!(dash.flac.is_none());
+}
+
+#[tokio::test]
+async fn test_pgc_play_url() {
+    let server = MockServer::start(
+        &[],
+        r#"{"code":0,"message":"success","result":{"quality":80,"format":"flv","timelength":360000,"video_codecid":7,"is_preview":1,"record":"《某番剧》","clip_info_list":[{"materialNo":0,"start":0,"end":90,"toastText":"即将跳过片头","clipType":"CLIP_TYPE_OP"}],"durl":[{"order":1,"length":360000,"size":100,"url":"https://upos/ep.flv","backup_url":null}]}}"#,
+    )
+    .await;
+    let client = Client::builder().api_host(&server.url).build().unwrap();
+    let ep = crate::web::Ep {
+        ep_id: 1234,
+        cid: 5678,
+        ..Default::default()
+    };
+    let video_url = client
+        .ep_download_url(&ep, crate::Fnval::flv(), crate::VIDEO_QUALITY_1080P)
+        .await
+        .unwrap();
+    assert!(video_url.is_preview());
+    assert_eq!(video_url.record, "《某番剧》");
+    assert_eq!(video_url.clip_info_list[0].clip_type, "CLIP_TYPE_OP");
+    assert_eq!(video_url.clip_info_list[0].end, 90);
+    assert_eq!(video_url.durl[0].url, "https://upos/ep.flv");
+    let request = &server.requests()[0];
+    assert!(request.starts_with("GET /pgc/player/web/playurl?"));
+    assert!(request.contains("ep_id=1234") && request.contains("cid=5678"));
+    assert!(request.contains("qn=80") && !request.contains("bvid="));
+
+    let server = MockServer::start(
+        &[],
+        r#"{"code":0,"message":"success","result":{"video_info":{"quality":64,"timelength":1000,"is_preview":0}}}"#,
+    )
+    .await;
+    let client = Client::builder().api_host(&server.url).build().unwrap();
+    let video_url = client
+        .pgc_play_url_v2(&crate::PlayUrlRequest::ep(1234, 5678))
+        .await
+        .unwrap();
+    assert_eq!(video_url.quality, 64);
+    assert!(!video_url.is_preview());
+    assert!(server.requests()[0].starts_with("GET /pgc/player/web/v2/playurl?"));
+}
+
+#[tokio::test]
+async fn test_videos_info_api() {
+    let season = r#"{"code":0,"message":"success","result":{"season_id":33,"media_id":28,"type":1,"title":"某番剧","season_title":"第一季","evaluate":"简介","styles":["日常"],"areas":[{"id":2,"name":"日本"}],"rating":{"count":10,"score":9.5},"stat":{"coins":1,"danmakus":2,"favorite":3,"favorites":4,"likes":5,"reply":6,"share":7,"views":8},"rights":{"allow_download":1,"area_limit":0,"can_watch":1,"copyright":"bilibili","is_preview":1},"publish":{"is_finish":1,"is_started":1,"pub_time":"2020-01-01 00:00:00","pub_time_show":"2020年01月01日"},"new_ep":{"id":102,"title":"2","desc":"已完结, 全2话","is_new":0},"episodes":[{"aid":1,"bvid":"BV1xx411c7mD","cid":11,"ep_id":101,"id":101,"title":"1","long_title":"开始","duration":1420000,"badge_info":{"bg_color":"","bg_color_night":"","text":""},"rights":{"allow_demand":0,"allow_dm":1,"allow_download":1,"area_limit":0},"skip":{"op":{"start":0,"end":90}}},{"aid":2,"cid":12,"ep_id":102,"id":102,"title":"2","long_title":"结束"}],"section":[{"id":5,"title":"PV","type":1,"episodes":[{"aid":3,"cid":13,"ep_id":201,"id":201,"title":"PV1"}]}],"seasons":[{"season_id":33,"season_title":"第一季","stat":{"views":8,"series_follow":9}}]}}"#.to_string();
+    let server = MockServer::start_with(move |request| {
+        let body = if request.starts_with("GET /pgc/review/user") {
+            r#"{"code":0,"message":"success","result":{"media":{"media_id":28,"season_id":33,"title":"某番剧","type_name":"番剧"}}}"#.to_string()
+        } else if request.starts_with("GET /pgc/view/web/season") {
+            season.clone()
+        } else {
+            "<html></html>".to_string()
+        };
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        )
+        .into_bytes()
+    })
+    .await;
+    let client = Client::builder()
+        .api_host(&server.url)
+        .www_host(&server.url)
+        .build()
+        .unwrap();
+    let ss = client.videos_info("ep101".to_string()).await.unwrap();
+    assert_eq!(ss.media_info.season_id, 33);
+    assert_eq!(ss.media_info.title, "某番剧");
+    assert_eq!(ss.media_info.rating.score, 9.5);
+    assert!(ss.media_info.publish.is_finish);
+    assert!(ss.rights.is_preview && ss.rights.can_watch);
+    assert_eq!(ss.init_ep_list.len(), 2);
+    assert_eq!(ss.init_ep_list[0].title_format, "第1话");
+    assert!(ss.init_ep_list[0].has_skip && ss.init_ep_list[0].has_next);
+    assert!(!ss.init_ep_list[1].has_next);
+    assert_eq!(ss.ep_map.len(), 3);
+    assert_eq!(ss.ep_map["201"].section_type, 1);
+    assert_eq!(ss.public_order_section_ids, vec![5]);
+    assert_eq!(ss.season_list[0].series_follow, 9);
+    assert!(server.requests()[0].starts_with("GET /pgc/view/web/season?ep_id=101 "));
+
+    let ss = client.videos_info("md28".to_string()).await.unwrap();
+    assert_eq!(ss.media_info.media_id, 28);
+    let requests = server.requests();
+    assert!(requests[1].starts_with("GET /pgc/review/user?media_id=28 "));
+    assert!(requests[2].starts_with("GET /pgc/view/web/season?season_id=33 "));
+
+    // 接口失败时解析网页, 都失败时返回接口的错误
+    let server = MockServer::start(&[], r#"{"code":-404,"message":"啥都木有"}"#).await;
+    let client = Client::builder()
+        .api_host(&server.url)
+        .www_host(&server.url)
+        .build()
+        .unwrap();
+    let err = client.videos_info("ss1".to_string()).await.unwrap_err();
+    assert!(matches!(err, Error::NotFound { .. }), "{:?}", err);
+    let requests = server.requests();
+    assert_eq!(requests.len(), 2);
+    assert!(requests[1].starts_with("GET /bangumi/play/ss1 "));
+}
+
+#[test]
+fn test_parse_resource() {
+    use crate::{parse_resource, Resource};
+
+    assert_eq!(
+        parse_resource("https://www.bilibili.com/video/BV1xx411c7mD?p=3&t=12.5").unwrap(),
+        Resource::Video {
+            bvid: "BV1xx411c7mD".to_string(),
+            page: Some(3),
+            time: Some(12.5),
+        }
+    );
+    assert_eq!(
+        parse_resource("BV1xx411c7mD").unwrap(),
+        Resource::Video {
+            bvid: "BV1xx411c7mD".to_string(),
+            page: None,
+            time: None,
+        }
+    );
+    assert!(matches!(
+        parse_resource("av170001").unwrap(),
+        Resource::Video { page: None, .. }
+    ));
+    assert!(matches!(
+        parse_resource("https://www.bilibili.com/video/av170001/?p=2").unwrap(),
+        Resource::Video { page: Some(2), .. }
+    ));
+    assert_eq!(
+        parse_resource("https://www.bilibili.com/bangumi/play/ep123?t=30").unwrap(),
+        Resource::Episode {
+            ep_id: 123,
+            time: Some(30.0),
+        }
+    );
+    assert_eq!(
+        parse_resource("ss123").unwrap(),
+        Resource::Season { season_id: 123 }
+    );
+    assert_eq!(
+        parse_resource("https://www.bilibili.com/bangumi/media/md28").unwrap(),
+        Resource::Media { media_id: 28 }
+    );
+    assert_eq!(
+        parse_resource("https://space.bilibili.com/2/favlist?fid=100&ftype=create").unwrap(),
+        Resource::FavList { mid: 2, fid: 100 }
+    );
+    assert_eq!(
+        parse_resource("https://space.bilibili.com/2/channel/collectiondetail?sid=7").unwrap(),
+        Resource::Collection { mid: 2, sid: 7 }
+    );
+    assert_eq!(
+        parse_resource("https://space.bilibili.com/2/channel/seriesdetail?sid=8&ctype=0").unwrap(),
+        Resource::Series { mid: 2, sid: 8 }
+    );
+    assert_eq!(
+        parse_resource("https://space.bilibili.com/2").unwrap(),
+        Resource::User { mid: 2 }
+    );
+    assert_eq!(
+        parse_resource("b23.tv/abcdef").unwrap(),
+        Resource::ShortLink {
+            url: "https://b23.tv/abcdef".to_string(),
+        }
+    );
+    assert!(matches!(
+        parse_resource("https://example.com/"),
+        Err(Error::InvalidArgument(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_resolve_resource() {
+    let server = MockServer::start_with(|request| {
+        if request.starts_with("GET /abcdef ") {
+            b"HTTP/1.1 302 Found\r\nLocation: /video/BV1xx411c7mD?p=2&share_source=copy\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_vec()
+        } else {
+            b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_vec()
+        }
+    })
+    .await;
+    let mut client = Client::new();
+    client.login_set_web_token(&WebToken {
+        dedeuserid: 1,
+        dedeuserid_ckmd5: "2".to_string(),
+        sessdata: "4".to_string(),
+        bili_jct: "5".to_string(),
+        expires: 3,
+    });
+    assert_eq!(
+        client
+            .resolve_short_link(&format!("{}/abcdef", server.url))
+            .await
+            .unwrap(),
+        crate::Resource::Video {
+            bvid: "BV1xx411c7mD".to_string(),
+            page: Some(2),
+            time: None,
+        }
+    );
+    assert!(client
+        .resolve_short_link(&format!("{}/other", server.url))
+        .await
+        .is_err());
+    // 短链接的地址不可信, 不能把会话的Cookie发送出去
+    assert!(server
+        .requests()
+        .iter()
+        .all(|request| !request.to_lowercase().contains("\r\ncookie:")));
+}
+
+#[test]
+fn test_av_bv() {
+    use crate::{av_to_bv, bv_to_av, VideoId};
+
+    assert_eq!(av_to_bv(170001).unwrap(), "BV17x411w7KC");
+    assert_eq!(bv_to_av("BV17x411w7KC").unwrap(), 170001);
+    assert_eq!(av_to_bv(1).unwrap(), "BV1xx411c7mQ");
+    // 超过2^30的AV号
+    let bvid = av_to_bv(1_650_000_000_000).unwrap();
+    assert_eq!(bv_to_av(&bvid).unwrap(), 1_650_000_000_000);
+    assert_eq!(
+        bv_to_av(&av_to_bv((1 << 51) - 1).unwrap()).unwrap(),
+        (1 << 51) - 1
+    );
+    assert!(av_to_bv(0).is_err());
+    assert!(av_to_bv(1 << 51).is_err());
+    assert!(bv_to_av("BV17x411w7K").is_err());
+    assert!(bv_to_av("BV17x411w7K0").is_err());
+    assert!(bv_to_av("BV17x411w7K码").is_err());
+
+    let id: VideoId = "av170001".parse().unwrap();
+    assert_eq!(id.bvid(), "BV17x411w7KC");
+    assert_eq!(id, "bv17x411w7KC".parse().unwrap());
+    assert_eq!(id, "170001".parse().unwrap());
+    assert_eq!(id, VideoId::try_from(170001).unwrap());
+    assert_eq!(id.aid(), 170001);
+    assert_eq!(id.to_string(), "BV17x411w7KC");
+    assert!("视频".parse::<VideoId>().is_err());
+    assert!("BV1".parse::<VideoId>().is_err());
+}
+
+#[tokio::test]
+async fn test_paging_streams() {
+    fn query(request: &str, name: &str) -> i64 {
+        let pattern = format!("[?&]{}=(\\d+)", name);
+        regex::Regex::new(&pattern)
+            .unwrap()
+            .captures(request)
+            .unwrap()[1]
+            .parse()
+            .unwrap()
+    }
+    fn archives(start: i64, end: i64) -> String {
+        (start..end)
+            .map(|aid| {
+                format!(
+                    r#"{{"aid":{},"bvid":"","ctime":0,"duration":0,"interactive_video":false,"pic":"","pubdate":0,"stat":{{"view":0}},"state":0,"title":"","ugc_pay":0}}"#,
+                    aid
+                )
+            })
+            .collect::<Vec<String>>()
+            .join(",")
+    }
+    let server = MockServer::start_with(|request| {
+        let body = if request.starts_with("GET /x/polymer/space/seasons_archives_list") {
+            let (page_num, page_size) = (query(request, "page_num"), query(request, "page_size"));
+            let start = (page_num - 1) * page_size;
+            format!(
+                r#"{{"code":0,"message":"0","data":{{"aids":[],"archives":[{}],"meta":{{"category":0,"cover":"","description":"","mid":1,"name":"","ptime":0,"season_id":2,"total":65}},"page":{{"page_num":{},"page_size":{},"total":65}}}}}}"#,
+                archives(start, (start + page_size).min(65)),
+                page_num,
+                page_size
+            )
+        } else if request.starts_with("GET /x/series/archives") {
+            let (pn, ps) = (query(request, "pn"), query(request, "ps"));
+            if pn == 2 {
+                r#"{"code":-404,"message":"啥都木有"}"#.to_string()
+            } else {
+                format!(
+                    r#"{{"code":0,"message":"0","data":{{"aids":[],"archives":[{}],"page":{{"num":{},"size":{},"total":100}}}}}}"#,
+                    archives((pn - 1) * ps, pn * ps),
+                    pn,
+                    ps
+                )
+            }
+        } else {
+            let (pn, ps) = (query(request, "pn"), query(request, "ps"));
+            let medias = (0..if pn == 2 { 5 } else { ps })
+                .map(|index| {
+                    format!(
+                        r#"{{"id":{},"type":2,"title":"","cover":"","intro":"","page":1,"duration":0,"upper":{{"mid":0,"name":"","face":""}},"attr":0,"cnt_info":{{"collect":0,"play":0,"danmaku":0,"vt":0,"play_switch":0,"reply":0}},"link":"","ctime":0,"pubtime":0,"fav_time":0,"bv_id":"","bvid":"","ogv":null,"ugc":null}}"#,
+                        (pn - 1) * ps + index
+                    )
+                })
+                .collect::<Vec<String>>()
+                .join(",");
+            // media_count包含已失效的视频, 以has_more为准
+            format!(
+                r#"{{"code":0,"message":"0","data":{{"info":{{"id":1,"fid":1,"mid":1,"attr":0,"title":"","cover":"","upper":{{"mid":0,"name":"","face":"","followed":false,"vip_type":0,"vip_statue":0}},"cover_type":0,"cnt_info":{{"collect":0,"play":0,"thumb_up":0,"share":0}},"type":11,"intro":"","ctime":0,"mtime":0,"state":0,"fav_state":0,"like_state":0,"media_count":30}},"medias":[{}],"has_more":{},"ttl":1}}}}"#,
+                medias,
+                pn == 1
+            )
+        };
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        )
+        .into_bytes()
+    })
+    .await;
+    let client = Client::builder().api_host(&server.url).build().unwrap();
+
+    let videos: Vec<crate::VideoArchive> = client
+        .collection_videos_stream(1, 2, false, crate::PageOptions::new().concurrency(3))
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(
+        videos.iter().map(|video| video.aid).collect::<Vec<i64>>(),
+        (0..65).collect::<Vec<i64>>()
+    );
+    assert_eq!(server.requests().len(), 3);
+
+    let medias: Vec<crate::FavListMedia> = client
+        .fav_list_stream(
+            1,
+            None,
+            crate::FavListOrder::Mtime,
+            crate::PageOptions::new().page_size(20),
+        )
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(medias.len(), 25);
+    assert_eq!(medias[24].id, 24);
+    assert_eq!(server.requests().len(), 5);
+
+    let results: Vec<crate::Result<crate::VideoArchive>> = client
+        .series_videos_stream(1, 3, false, crate::PageOptions::new().page_size(40))
+        .collect()
+        .await;
+    assert_eq!(results.len(), 41);
+    assert!(results[..40].iter().all(|result| result.is_ok()));
+    assert!(matches!(results[40], Err(Error::NotFound { .. })));
+}
@@ -1,6 +1,10 @@
+use chrono::NaiveDate;
 use crypto::digest::Digest;
 
-use crate::{Error, Result};
+use crate::{
+    web, BvInfo, CollectionDetailPage, Error, FavListPage, Result, SeriesVideoInfoData, UserInfo,
+    WbiImg,
+};
 
 //////////////////// JSON ////////////////////
 
@@ -11,28 +15,258 @@ pub fn from_str<T: for<'de> serde::Deserialize<'de>>(json: &str) -> Result<T> {
     )?)
 }
 
+/// FROM VALUE 并打印出错的位置
 pub fn from_value<T: for<'de> serde::Deserialize<'de>>(json: serde_json::Value) -> Result<T> {
     Ok(serde_path_to_error::deserialize(json)?)
 }
 
-//////////////////// AV->BV ////////////////////
+//////////////////// AV<->BV ////////////////////
 
-const AV_TABLE: &'static str = "fZodR9XQDSUm21yCkr6zBqiveYah8bt4xsWpHnJE7jL5VG3guMTKNPAwcF";
-const XOR: i64 = 177451812;
-const ADD: i64 = 100618342136696320;
-const S: [usize; 10] = [9, 8, 1, 6, 2, 4, 0, 7, 3, 5];
+const BV_TABLE: &[u8; 58] = b"FcwAPNKTMug3GV5Lj7EJnHpWsx4tb8haYeviqBz6rkCy12mUSDQX9RdoZf";
+const XOR_CODE: i64 = 23442827791579;
+const MASK_CODE: i64 = 2251799813685247;
+const MAX_AID: i64 = 1 << 51;
+const BV_LEN: usize = 12;
 
-pub fn av_to_bv(avid: i64) -> String {
-    let table = AV_TABLE.as_bytes();
-    let x1 = (avid ^ XOR) + ADD;
-    let mut pow = 1;
-    let mut bytes = vec![0; 10];
-    for i in 0..10 {
-        let index = x1 / pow % 58;
-        bytes[S[i]] = table[index as usize];
-        pow *= 58;
+/// AV号转BV号, avid需要在 1..2^51 之间
+pub fn av_to_bv(avid: i64) -> Result<String> {
+    if avid <= 0 || avid >= MAX_AID {
+        return Err(Error::InvalidArgument(format!("AV号超出范围: {}", avid)));
     }
-    format!("BV{}", String::from_utf8(bytes).unwrap())
+    let mut bytes = *b"BV1000000000";
+    let mut tmp = (MAX_AID | avid) ^ XOR_CODE;
+    let mut index = BV_LEN - 1;
+    while tmp > 0 {
+        bytes[index] = BV_TABLE[(tmp % 58) as usize];
+        tmp /= 58;
+        index -= 1;
+    }
+    bytes.swap(3, 9);
+    bytes.swap(4, 7);
+    Ok(bytes.iter().map(|b| *b as char).collect())
+}
+
+/// BV号转AV号
+pub fn bv_to_av(bvid: &str) -> Result<i64> {
+    let invalid = || Error::InvalidArgument(format!("BV号格式错误: {}", bvid));
+    let mut bytes: Vec<u8> = bvid.bytes().collect();
+    if bytes.len() != BV_LEN || !bytes[..2].eq_ignore_ascii_case(b"BV") || bytes[2] != b'1' {
+        return Err(invalid());
+    }
+    bytes.swap(3, 9);
+    bytes.swap(4, 7);
+    let mut tmp: i64 = 0;
+    for b in &bytes[3..] {
+        let index = BV_TABLE.iter().position(|t| t == b).ok_or_else(invalid)?;
+        tmp = tmp * 58 + index as i64;
+    }
+    let avid = (tmp & MASK_CODE) ^ XOR_CODE;
+    if avid <= 0 {
+        return Err(invalid());
+    }
+    Ok(avid)
+}
+
+/// 视频编号, 可以从BV号或AV号 (av170001 或 170001) 构造, 统一保存为BV号
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct VideoId(String);
+
+impl VideoId {
+    pub fn from_aid(avid: i64) -> Result<Self> {
+        Ok(VideoId(av_to_bv(avid)?))
+    }
+
+    pub fn from_bvid(bvid: &str) -> Result<Self> {
+        // 重新编码以检查是否合法, 并统一前缀为 "BV"
+        let id = Self::from_aid(bv_to_av(bvid)?)?;
+        if id.0[2..] != bvid[2..] {
+            return Err(Error::InvalidArgument(format!("BV号格式错误: {}", bvid)));
+        }
+        Ok(id)
+    }
+
+    pub fn bvid(&self) -> &str {
+        &self.0
+    }
+
+    pub fn aid(&self) -> i64 {
+        bv_to_av(&self.0).unwrap()
+    }
+}
+
+impl std::str::FromStr for VideoId {
+    type Err = Error;
+
+    fn from_str(id: &str) -> Result<Self> {
+        let id = id.trim();
+        let prefix = id.get(..2).unwrap_or_default();
+        if prefix.eq_ignore_ascii_case("bv") {
+            return Self::from_bvid(id);
+        }
+        let avid = if prefix.eq_ignore_ascii_case("av") {
+            &id[2..]
+        } else {
+            id
+        };
+        match avid.parse::<i64>() {
+            Ok(avid) => Self::from_aid(avid),
+            Err(_) => Err(Error::InvalidArgument(format!("视频编号格式错误: {}", id))),
+        }
+    }
+}
+
+impl TryFrom<i64> for VideoId {
+    type Error = Error;
+
+    fn try_from(avid: i64) -> Result<Self> {
+        Self::from_aid(avid)
+    }
+}
+
+impl std::fmt::Display for VideoId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+////////////////////////////////////////////////
+
+/////////////////// RESOURCE ///////////////////
+
+/// 链接或编号对应的资源
+#[derive(Debug, Clone, PartialEq)]
+pub enum Resource {
+    /// 视频 (BV/AV), page为分P (从1开始), time为开始播放的时间 (秒, t=)
+    Video {
+        bvid: String,
+        page: Option<i64>,
+        time: Option<f64>,
+    },
+    /// 番剧/电影的剧集 (ep)
+    Episode { ep_id: i64, time: Option<f64> },
+    /// 番剧/电影 (ss)
+    Season { season_id: i64 },
+    /// 番剧/电影的条目 (md)
+    Media { media_id: i64 },
+    /// 收藏夹
+    FavList { mid: i64, fid: i64 },
+    /// 合集
+    Collection { mid: i64, sid: i64 },
+    /// 系列
+    Series { mid: i64, sid: i64 },
+    /// 用户空间
+    User { mid: i64 },
+    /// 短链接 (b23.tv), 需要使用Client::resolve_resource跟随跳转
+    ShortLink { url: String },
+}
+
+/// 资源对应接口的结果, 列表类的资源为第一页
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResourceInfo {
+    /// bv_info
+    Video(Box<BvInfo>),
+    /// videos_info
+    Season(Box<web::SsState>),
+    /// fav_list_page
+    FavList(Box<FavListPage>),
+    /// collection_video_page
+    Collection(Box<CollectionDetailPage>),
+    /// series_info
+    Series(Box<SeriesVideoInfoData>),
+    /// user_info
+    User(Box<UserInfo>),
+}
+
+/// 只在第一次使用时编译的正则
+macro_rules! regex {
+    ($pattern:expr) => {{
+        static REGEX: std::sync::OnceLock<regex::Regex> = std::sync::OnceLock::new();
+        REGEX.get_or_init(|| regex::Regex::new($pattern).unwrap())
+    }};
+}
+
+/// 解析链接或编号, 支持:
+/// b23.tv短链接, BV/AV号及视频链接 (?p=, t=), ep/ss/md号及番剧链接,
+/// 用户空间的收藏夹 (favlist?fid=), 合集 (collectiondetail?sid=), 系列 (seriesdetail?sid=)
+pub fn parse_resource(input: &str) -> Result<Resource> {
+    let input = input.trim();
+    let number = |value: &str| {
+        value
+            .parse::<i64>()
+            .map_err(|err| Error::InvalidArgument(format!("{}: {}", input, err)))
+    };
+    let query = |regex: &regex::Regex| {
+        regex
+            .captures(input)
+            .map(|captures| captures[1].to_string())
+    };
+
+    if regex!("^(https?://)?(b23\\.tv|bili2233\\.cn)/").is_match(input) {
+        let url = if input.starts_with("http") {
+            input.to_string()
+        } else {
+            format!("https://{}", input)
+        };
+        return Ok(Resource::ShortLink { url });
+    }
+    if let Some(c) =
+        regex!("space\\.bilibili\\.com/(\\d+)/favlist\\?(.*&)?fid=(\\d+)").captures(input)
+    {
+        return Ok(Resource::FavList {
+            mid: number(&c[1])?,
+            fid: number(&c[3])?,
+        });
+    }
+    if let Some(c) =
+        regex!("space\\.bilibili\\.com/(\\d+)/channel/(collection|series)detail\\?(.*&)?sid=(\\d+)")
+            .captures(input)
+    {
+        let (mid, sid) = (number(&c[1])?, number(&c[4])?);
+        return Ok(if &c[2] == "collection" {
+            Resource::Collection { mid, sid }
+        } else {
+            Resource::Series { mid, sid }
+        });
+    }
+    if let Some(c) = regex!("space\\.bilibili\\.com/(\\d+)").captures(input) {
+        return Ok(Resource::User {
+            mid: number(&c[1])?,
+        });
+    }
+    let time = match query(regex!("[?&]t=([0-9.]+)")) {
+        Some(time) => Some(
+            time.parse::<f64>()
+                .map_err(|err| Error::InvalidArgument(format!("{}: {}", input, err)))?,
+        ),
+        None => None,
+    };
+    if let Some(c) = regex!("(^|[/=])(BV[0-9A-Za-z]{10})").captures(input) {
+        return Ok(Resource::Video {
+            bvid: c[2].to_string(),
+            page: query(regex!("[?&]p=([0-9.]+)"))
+                .map(|page| number(&page))
+                .transpose()?,
+            time,
+        });
+    }
+    if let Some(c) = regex!("(?i)(^|[/=])av(\\d+)").captures(input) {
+        return Ok(Resource::Video {
+            bvid: av_to_bv(number(&c[2])?)?,
+            page: query(regex!("[?&]p=([0-9.]+)"))
+                .map(|page| number(&page))
+                .transpose()?,
+            time,
+        });
+    }
+    if let Some(c) = regex!("(?i)(^|/)(ep|ss|md)(\\d+)").captures(input) {
+        let id = number(&c[3])?;
+        return Ok(match c[2].to_lowercase().as_str() {
+            "ep" => Resource::Episode { ep_id: id, time },
+            "ss" => Resource::Season { season_id: id },
+            _ => Resource::Media { media_id: id },
+        });
+    }
+    Err(Error::InvalidArgument(format!("无法识别的链接: {}", input)))
 }
 
 ////////////////////////////////////////////////
@@ -42,7 +276,7 @@ pub fn av_to_bv(avid: i64) -> String {
 pub fn sign_form(params: serde_json::Value, app_sec: &str) -> Result<serde_json::Value> {
     // 获取签名参数的对象
     if !params.is_object() {
-        return Err(Error::msg("sign error: not object"));
+        return Err(Error::InvalidArgument("sign error: not object".to_string()));
     }
     let obj = params.as_object().unwrap();
     // 获取Keys并排序
@@ -58,7 +292,7 @@ pub fn sign_form(params: serde_json::Value, app_sec: &str) -> Result<serde_json:
         if item.is_string() {
             link_list.push(format!("{}={}", key, item.as_str().unwrap()))
         } else {
-            link_list.push(format!("{}={}", key, item.to_string()))
+            link_list.push(format!("{}={}", key, item))
         }
     }
     let link_list = format!("{}{}", link_list.join("&"), app_sec);
@@ -73,3 +307,107 @@ pub fn sign_form(params: serde_json::Value, app_sec: &str) -> Result<serde_json:
 }
 
 ////////////////////////////////////////////////
+
+///////////////////// WBI /////////////////////
+
+const MIXIN_KEY_ENC_TAB: [usize; 64] = [
+    46, 47, 18, 2, 53, 8, 23, 32, 15, 50, 10, 31, 58, 3, 45, 35, 27, 43, 5, 49, 33, 9, 42, 19, 29,
+    28, 14, 39, 12, 38, 41, 13, 37, 48, 7, 16, 24, 55, 40, 61, 26, 17, 0, 1, 60, 51, 30, 4, 22, 25,
+    54, 21, 56, 59, 6, 63, 57, 62, 11, 36, 20, 34, 44, 52,
+];
+
+/// WBI签名使用的密钥, 来自 /x/web-interface/nav 的 wbi_img, 每日更新
+#[derive(Debug, Clone, PartialEq)]
+pub struct WbiKeys {
+    pub img_key: String,
+    pub sub_key: String,
+    /// 获取密钥的日期, 与当前日期不同时需要重新获取
+    pub date: NaiveDate,
+}
+
+impl WbiKeys {
+    /// 从 wbi_img 的图片地址中取出密钥
+    /// 例如 https://i0.hdslb.com/bfs/wbi/7cd084941338484aae1ad9425b84077c.png
+    pub fn from_wbi_img(wbi_img: &WbiImg) -> Result<Self> {
+        Ok(Self {
+            img_key: wbi_key_from_url(&wbi_img.img_url)?,
+            sub_key: wbi_key_from_url(&wbi_img.sub_url)?,
+            date: chrono::Local::now().date_naive(),
+        })
+    }
+
+    /// 密钥是否已经过期 (非当天获取)
+    pub fn expired(&self) -> bool {
+        self.date != chrono::Local::now().date_naive()
+    }
+
+    /// 对参数进行签名, 增加 wts 与 w_rid
+    pub fn sign(&self, params: serde_json::Value) -> Result<serde_json::Value> {
+        wbi_sign(
+            params,
+            &wbi_mixin_key(&self.img_key, &self.sub_key)?,
+            chrono::Local::now().timestamp(),
+        )
+    }
+}
+
+fn wbi_key_from_url(url: &str) -> Result<String> {
+    let name = url.rsplit('/').next().unwrap_or_default();
+    let key = name.split('.').next().unwrap_or_default();
+    if key.is_empty() {
+        return Err(Error::Format(format!("wbi_img格式错误: {}", url)));
+    }
+    Ok(key.to_string())
+}
+
+/// 将 img_key 与 sub_key 按照混淆表重排得到 mixin_key
+pub fn wbi_mixin_key(img_key: &str, sub_key: &str) -> Result<String> {
+    let raw = format!("{}{}", img_key, sub_key).into_bytes();
+    if raw.len() < MIXIN_KEY_ENC_TAB.len() {
+        return Err(Error::InvalidArgument(format!(
+            "wbi key长度错误: {}",
+            raw.len()
+        )));
+    }
+    Ok(MIXIN_KEY_ENC_TAB[..32]
+        .iter()
+        .map(|i| raw[*i] as char)
+        .collect())
+}
+
+/// WBI签名
+/// 参数加入wts后按key排序, 过滤值中的 !'()* 字符, 进行URL编码后拼接mixin_key计算MD5作为w_rid
+pub fn wbi_sign(params: serde_json::Value, mixin_key: &str, wts: i64) -> Result<serde_json::Value> {
+    let mut obj = match params {
+        serde_json::Value::Object(obj) => obj,
+        serde_json::Value::Null => serde_json::Map::new(),
+        _ => return Err(Error::InvalidArgument("sign error: not object".to_string())),
+    };
+    obj.insert("wts".to_string(), serde_json::Value::from(wts));
+    let mut key_list: Vec<String> = obj.keys().cloned().collect();
+    key_list.sort();
+    let mut link_list: Vec<String> = vec![];
+    for key in key_list {
+        let item = obj.get(&key).unwrap();
+        let value = match item.as_str() {
+            Some(value) => value.to_string(),
+            None => item.to_string(),
+        };
+        let value: String = value.chars().filter(|c| !"!'()*".contains(*c)).collect();
+        link_list.push(format!(
+            "{}={}",
+            urlencoding::encode(&key),
+            urlencoding::encode(&value)
+        ));
+        obj.insert(key, serde_json::Value::String(value));
+    }
+    let mut hasher = crypto::md5::Md5::new();
+    hasher.input_str(&format!("{}{}", link_list.join("&"), mixin_key));
+    obj.insert(
+        "w_rid".to_string(),
+        serde_json::Value::String(hasher.result_str()),
+    );
+    Ok(serde_json::Value::Object(obj))
+}
+
+////////////////////////////////////////////////